@@ -0,0 +1,227 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::format::{self, OutputFormat};
+
+/// READMEと並んで優先的に含めるマニフェストファイル
+const MANIFEST_FILES: [&str; 18] = [
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "setup.cfg",
+    "requirements.txt",
+    "go.mod",
+    "Gemfile",
+    "composer.json",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "CMakeLists.txt",
+    "Makefile",
+    "Dockerfile",
+    "mix.exs",
+    "Package.swift",
+    "pubspec.yaml",
+];
+
+/// トークン予算の割り当て対象となるファイル
+#[derive(Debug, Clone)]
+pub struct Candidate<T> {
+    /// ファイルのパス
+    pub path: PathBuf,
+    /// Gitルートからの相対パス
+    pub rel_path: String,
+    /// 出力した場合のトークン数
+    pub tokens: usize,
+    /// 出力内容
+    pub item: T,
+}
+
+/// トークン予算に収まらず省略されたファイル
+#[derive(Debug, Clone, Serialize)]
+pub struct Omitted {
+    /// Gitルートからの相対パス
+    pub path: String,
+    /// 出力した場合のトークン数
+    pub tokens: usize,
+}
+
+/// 予算内に収めた結果
+#[derive(Debug)]
+pub struct Packing<T> {
+    /// 予算内に収まったファイルの出力内容（元の順序を保つ）
+    pub selected: Vec<T>,
+    /// 省略されたファイル
+    pub omitted: Vec<Omitted>,
+}
+
+/// ファイルの優先順位
+pub struct Priority {
+    /// パターンの基準となるGitリポジトリのルート（なしの場合は候補の`rel_path`にマッチさせる）
+    root: Option<PathBuf>,
+    /// パス全体にマッチさせるglob
+    path_globs: GlobSet,
+    /// ファイル名にマッチさせるglob（`/`を含まないパターン）
+    name_globs: GlobSet,
+    /// 明示的に指定されたファイル
+    named_paths: Vec<PathBuf>,
+}
+
+impl Priority {
+    /// ユーザー指定のglobと明示的に指定されたパスから優先順位を作成する
+    /// （globは`--include`/`--exclude`と同じくGitリポジトリのルートからの相対パスにマッチさせる）
+    pub fn new<P: AsRef<Path>>(
+        root: Option<&Path>,
+        patterns: &[String],
+        named_paths: &[P],
+    ) -> Result<Self> {
+        let mut path_globs = GlobSetBuilder::new();
+        let mut name_globs = GlobSetBuilder::new();
+
+        for pattern in patterns {
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .context(format!("Invalid glob pattern: {}", pattern))?;
+
+            if pattern.contains('/') {
+                path_globs.add(glob);
+            } else {
+                name_globs.add(glob);
+            }
+        }
+
+        let named_paths = named_paths
+            .iter()
+            .filter_map(|p| p.as_ref().canonicalize().ok())
+            .filter(|p| p.is_file())
+            .collect();
+
+        Ok(Self {
+            root: root.map(Path::to_path_buf),
+            path_globs: path_globs.build()?,
+            name_globs: name_globs.build()?,
+            named_paths,
+        })
+    }
+
+    /// 優先度を返す（小さいほど優先）
+    fn rank<T>(&self, candidate: &Candidate<T>) -> usize {
+        let rel_path = self
+            .root
+            .as_ref()
+            .and_then(|root| candidate.path.strip_prefix(root).ok())
+            .map(|rel| rel.to_string_lossy().replace('\\', "/"))
+            .unwrap_or_else(|| candidate.rel_path.clone());
+        let file_name = Path::new(&rel_path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if self.path_globs.is_match(&rel_path) || self.name_globs.is_match(&file_name) {
+            0
+        } else if self.is_named(&candidate.path) {
+            1
+        } else if is_readme_or_manifest(&file_name) {
+            2
+        } else {
+            3
+        }
+    }
+
+    /// 明示的に指定されたファイルかどうか
+    fn is_named(&self, path: &Path) -> bool {
+        if self.named_paths.is_empty() {
+            return false;
+        }
+
+        path.canonicalize()
+            .map(|p| self.named_paths.contains(&p))
+            .unwrap_or(false)
+    }
+}
+
+/// READMEまたはマニフェストファイルかどうか
+fn is_readme_or_manifest(file_name: &str) -> bool {
+    file_name.to_lowercase().starts_with("readme") || MANIFEST_FILES.contains(&file_name)
+}
+
+/// 優先度の高い順（同じ優先度ならトークン数の少ない順）に予算内に収まるファイルを選ぶ
+pub fn pack<T>(candidates: Vec<Candidate<T>>, budget: usize, priority: &Priority) -> Packing<T> {
+    let mut order = (0..candidates.len()).collect::<Vec<_>>();
+    order.sort_by_key(|&i| (priority.rank(&candidates[i]), candidates[i].tokens, i));
+
+    let mut remaining = budget;
+    let mut keep = vec![false; candidates.len()];
+    for i in order {
+        if candidates[i].tokens <= remaining {
+            remaining -= candidates[i].tokens;
+            keep[i] = true;
+        }
+    }
+
+    let mut packing = Packing {
+        selected: Vec::new(),
+        omitted: Vec::new(),
+    };
+
+    for (candidate, keep) in candidates.into_iter().zip(keep) {
+        if keep {
+            packing.selected.push(candidate.item);
+        } else {
+            packing.omitted.push(Omitted {
+                path: candidate.rel_path,
+                tokens: candidate.tokens,
+            });
+        }
+    }
+
+    packing
+}
+
+/// 省略されたファイルの一覧を出力フォーマットに合わせて書き込む
+pub fn write_omitted<W: Write>(
+    writer: &mut W,
+    omitted: &[Omitted],
+    output_format: OutputFormat,
+) -> Result<()> {
+    if omitted.is_empty() {
+        return Ok(());
+    }
+
+    match output_format {
+        OutputFormat::Plain => {
+            writeln!(writer, "\n\nOmitted files (exceeded --max-tokens):")?;
+            for file in omitted {
+                writeln!(writer, "/{} ({} tokens)", file.path, file.tokens)?;
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(writer, "### Omitted files (exceeded --max-tokens)\n")?;
+            for file in omitted {
+                writeln!(writer, "- `{}` ({} tokens)", file.path, file.tokens)?;
+            }
+        }
+        OutputFormat::Xml => {
+            writeln!(writer, "<omitted_files>")?;
+            for file in omitted {
+                writeln!(
+                    writer,
+                    "<file path=\"{}\" tokens=\"{}\" />",
+                    format::escape_xml(&file.path),
+                    file.tokens
+                )?;
+            }
+            writeln!(writer, "</omitted_files>")?;
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            unreachable!("structured formats record omitted files in jsonview")
+        }
+    }
+
+    Ok(())
+}
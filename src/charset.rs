@@ -0,0 +1,170 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::gitutil;
+
+/// 文字コードの判定で確認する先頭のバイト数
+const DETECT_BYTES: usize = 8000;
+
+/// 作業ツリーのファイルの文字コードを指定するGit属性
+const WORKING_TREE_ENCODING: &str = "working-tree-encoding";
+
+/// ファイルの文字コード（`auto`またはWHATWGのラベル`utf-8`・`shift_jis`・`euc-jp`・`utf-16le`など）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Charset {
+    /// BOM・UTF-16の特徴・内容から推測する
+    #[default]
+    Auto,
+    /// 指定した文字コードとして読み込む
+    Fixed(&'static Encoding),
+}
+
+impl FromStr for Charset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Charset::Auto);
+        }
+
+        match Encoding::for_label(s.trim().as_bytes()) {
+            Some(encoding) => Ok(Charset::Fixed(encoding)),
+            None => anyhow::bail!(
+                "Unknown encoding: {} (use auto or a label such as utf-8, shift_jis, euc-jp, utf-16le)",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Charset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Charset::Auto => write!(f, "auto"),
+            Charset::Fixed(encoding) => write!(f, "{}", encoding.name().to_ascii_lowercase()),
+        }
+    }
+}
+
+impl Serialize for Charset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Charset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Charset {
+    /// ファイルの内容を読み込む文字コードを決定する
+    pub fn resolve(self, bytes: &[u8]) -> &'static Encoding {
+        match self {
+            Charset::Auto => detect(bytes),
+            Charset::Fixed(encoding) => encoding,
+        }
+    }
+
+    /// ファイルの先頭だけでUTF-8として読み込めると判断できるかどうか（BOMやUTF-16の特徴がない場合）
+    /// （`auto`では、残りの内容もUTF-8として正しい場合に限りUTF-8と推測される）
+    pub fn is_utf8_prefix(self, head: &[u8]) -> bool {
+        if Encoding::for_bom(head).is_some() {
+            return false;
+        }
+
+        match self {
+            Charset::Auto => detect_utf16(head).is_none(),
+            Charset::Fixed(encoding) => encoding == UTF_8,
+        }
+    }
+}
+
+/// 文字コードを推測する（BOM、UTF-16の特徴、UTF-8として正しいか、内容の統計の順に判定する）
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = detect_utf16(bytes) {
+        return encoding;
+    }
+
+    // NULを含むファイルはバイナリとして扱われるため推測しない
+    let sample = &bytes[..bytes.len().min(DETECT_BYTES)];
+    if sample.contains(&0) || std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, false)
+}
+
+/// BOMのないUTF-16を、ASCII文字の上位バイトのNULが偶数・奇数の一方の位置に偏ることから判定する
+fn detect_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let pairs = bytes[..bytes.len().min(DETECT_BYTES)]
+        .chunks_exact(2)
+        .collect::<Vec<_>>();
+    if pairs.is_empty() {
+        return None;
+    }
+
+    // 3割以上がASCII文字で、その他の位置のNULや制御文字がほとんどない場合をUTF-16とみなす
+    let is_text = |b: u8| b == b'\t' || b == b'\n' || b == b'\r' || (0x20..0x7f).contains(&b);
+    let looks_like = |high: usize, low: usize| {
+        let ascii = pairs
+            .iter()
+            .filter(|p| p[high] == 0 && is_text(p[low]))
+            .count();
+        let other = pairs
+            .iter()
+            .filter(|p| p[low] == 0 || (p[high] == 0 && !is_text(p[low])))
+            .count();
+        ascii * 10 >= pairs.len() * 3 && other * 20 <= pairs.len()
+    };
+
+    if looks_like(1, 0) {
+        Some(UTF_16LE)
+    } else if looks_like(0, 1) {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// UTF-16として読み込む文字コードかどうか（NULを含んでもバイナリとして扱わない）
+pub fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+/// 指定した文字コードでUTF-8に変換する（変換できないバイトはエラーにせず置換文字にする）
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> String {
+    let (text, _) = encoding.decode_with_bom_removal(bytes);
+    text.into_owned()
+}
+
+/// `.gitattributes`の`working-tree-encoding`で文字コードが指定されたファイルを取得する
+/// （未知のラベルは無視して推測に任せる）
+pub fn working_tree_encodings(
+    git_root: &Path,
+    files: &[PathBuf],
+) -> Result<HashMap<PathBuf, Charset>> {
+    let attributes = gitutil::check_attributes(git_root, files, &[WORKING_TREE_ENCODING], None)?;
+
+    Ok(attributes
+        .into_iter()
+        .filter_map(|(path, values)| {
+            let charset = values.get(WORKING_TREE_ENCODING)?.parse().ok()?;
+            Some((path, charset))
+        })
+        .collect())
+}
@@ -1,18 +1,184 @@
 use anyhow::{Context, Result};
 use arboard::Clipboard;
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use tiktoken_rs::cl100k_base;
+use std::sync::OnceLock;
 
-use crate::filefilter;
-use crate::fileview;
-use crate::gitutil;
-use crate::treeview;
+use crate::budget::{self, Candidate, Priority};
+use crate::charset::{self, Charset};
+use crate::filefilter::{self, AttributeFilter, Excluded, GlobFilter, IgnoreFiles};
+use crate::fileview::{self, DiffContent, Eol, LockfilePolicy, Normalize, Truncate, ViewOptions};
+use crate::format::{self, OutputFormat};
+use crate::generated::{RuleConfig, RuleSet};
+use crate::gitutil::{self, ChangeStatus, ChangedFile, DiffSpec, Pathspec, Snapshot};
+use crate::jsonview::{self, FileRecord};
+use crate::limits::{self, Limited, TotalBytes};
+use crate::parallel;
+use crate::region::Region;
+use crate::tokens::{Encoding, TokenCounter};
+use crate::treeview::{self, TreeNode};
+
+/// 実行オプション
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// ファイルごとの最大表示行数（0の場合は無制限）
+    pub max_lines: usize,
+    /// max_linesを超えるファイルの切り詰め方
+    pub truncate: Truncate,
+    /// 内容を表示するファイルの最大サイズ（バイト）
+    pub max_file_size: Option<u64>,
+    /// 1行の最大文字数（超える部分は短縮する）
+    pub max_line_length: Option<usize>,
+    /// 出力全体の最大バイト数（超える場合は以降のファイルを省略する）
+    pub max_total_bytes: Option<u64>,
+    /// ファイルの文字コード（`auto`の場合は`.gitattributes`の指定か推測に従う）
+    pub encoding: Charset,
+    /// 改行コードの扱い
+    pub eol: Eol,
+    /// タブを展開する幅（なしの場合はタブのまま）
+    pub tab_width: Option<usize>,
+    /// 行末の空白を取り除く
+    pub strip_trailing_ws: bool,
+    /// 連続する空行を1行にまとめる
+    pub squeeze_blank: bool,
+    /// ツリービューを表示しない
+    pub no_tree: bool,
+    /// ファイル内容を表示しない
+    pub no_content: bool,
+    /// 出力をクリップボードにコピーする
+    pub copy_to_clipboard: bool,
+    /// fzfでファイルを選択する
+    pub use_fzf: bool,
+    /// ファイルパスを絞り込む正規表現
+    pub filter_pattern: Option<String>,
+    /// 除外するファイルパスの正規表現
+    pub exclude_regex: Option<String>,
+    /// 含めるファイルのglobパターン（gitignoreと同じ書式）
+    pub include_globs: Vec<String>,
+    /// 除外するファイルのglobパターン（gitignoreと同じ書式）
+    pub exclude_globs: Vec<String>,
+    /// `.codicatignore`を無視する
+    pub no_ignore_file: bool,
+    /// トークン数を表示する
+    pub show_token_count: bool,
+    /// 自動生成ファイルを除外する
+    pub exclude_generated: bool,
+    /// `.gitattributes`で`linguist-vendored`が指定されたファイルを除外する
+    pub exclude_vendored: bool,
+    /// `.gitattributes`で`linguist-documentation`が指定されたファイルを除外する
+    pub exclude_docs: bool,
+    /// 設定ファイルで追加した自動生成ファイルのルール
+    pub generated_rules: Vec<RuleConfig>,
+    /// 無効にする自動生成ファイルのルール名
+    pub disabled_generated_rules: Vec<String>,
+    /// 除外したファイルとその理由を表示する
+    pub explain_excluded: bool,
+    /// ロックファイルの表示方法（指定時は`--exclude-generated`でもロックファイルを除外しない）
+    pub lockfiles: Option<LockfilePolicy>,
+    /// 出力フォーマット
+    pub format: OutputFormat,
+    /// 出力全体のトークン数の上限
+    pub max_tokens: Option<usize>,
+    /// トークン予算内で優先的に含めるファイルのglobパターン
+    pub priority_globs: Vec<String>,
+    /// ツリービューに各ファイル・ディレクトリのトークン数を表示する
+    pub tree_tokens: bool,
+    /// トークン数の多いファイルとディレクトリを上位N件表示する
+    pub top: Option<usize>,
+    /// トークン数の計算に使うエンコーディング
+    pub tokenizer: Encoding,
+    /// 差分モードの比較対象（指定時は変更されたファイルのみを対象にする）
+    pub diff: Option<DiffSpec>,
+    /// 差分モードで表示する内容
+    pub diff_content: DiffContent,
+    /// 作業ツリーの代わりに読み込むリビジョン（コミット・タグ・ブランチなど）
+    pub rev: Option<String>,
+    /// 未追跡（かつ無視されていない）ファイルも含める
+    pub untracked: bool,
+    /// 絞り込み前の全ファイルでツリーを構築し、選択されたファイルを強調表示する
+    pub full_tree: bool,
+    /// ファイルの読み込み・整形に使うワーカー数（0の場合はCPU数）
+    pub jobs: usize,
+}
+
+/// コマンドライン引数で指定された対象
+struct Target {
+    /// ツリーのルート（指定されたパスの共通の親）
+    root: PathBuf,
+    /// 指定されたパスのうち存在するもの（絶対パス）
+    paths: Vec<PathBuf>,
+    /// Gitリポジトリのルート（Gitリポジトリ外の場合はNone）
+    git_root: Option<PathBuf>,
+    /// gitコマンドに渡す対象範囲
+    pathspec: Pathspec,
+    /// `path:40-120`・`path#App::execute`で指定されたファイルの表示範囲
+    regions: HashMap<PathBuf, Region>,
+}
+
+/// 1回の実行で使う状態（`App::execute`の呼び出しごとに作成する）
+struct Run {
+    /// コマンドライン引数で指定された対象
+    target: Target,
+    /// 差分モードで変更されたファイル一覧
+    changes: Option<Vec<ChangedFile>>,
+    /// `--rev`で指定されたリビジョン（差分モードでは変更後のリビジョンかインデックス）のスナップショット
+    snapshot: Option<Snapshot>,
+    /// `--untracked`で含める未追跡ファイル一覧
+    untracked: Option<Vec<PathBuf>>,
+    /// `.gitattributes`の`working-tree-encoding`で指定されたファイルごとの文字コード
+    encodings: HashMap<PathBuf, Charset>,
+}
+
+impl Run {
+    /// 出力するパスの基準となるGitリポジトリのルートを返す
+    fn git_root(&self) -> Option<&Path> {
+        self.target.git_root.as_deref()
+    }
+
+    /// 出力するパス（Gitルートからの相対パス）を取得する
+    fn display_path(&self, file: &Path) -> String {
+        fileview::display_path(file, self.git_root())
+    }
+
+    /// 差分を取得するGitリポジトリのルートを返す（差分モードではGitリポジトリ内であることを確認済み）
+    fn diff_root(&self) -> Result<&Path> {
+        self.git_root()
+            .context("Diff mode requires a Git repository")
+    }
+
+    /// トークン予算内でファイルを含める優先順位を作成する
+    fn priority(&self, options: &Options) -> Result<Priority> {
+        Priority::new(self.git_root(), &options.priority_globs, &self.target.paths)
+    }
+
+    /// 差分モードで変更されたファイルの情報を取得する
+    fn change_for(&self, file: &Path) -> Option<&ChangedFile> {
+        self.changes.as_ref()?.iter().find(|c| c.path == file)
+    }
+
+    /// ツリーでファイルに付ける記号（差分モードの変更種別、未追跡ファイル）を返す
+    fn markers(&self) -> HashMap<PathBuf, String> {
+        let mut markers = HashMap::new();
+
+        for change in self.changes.iter().flatten() {
+            markers.insert(change.path.clone(), change.status.marker().to_string());
+        }
+        for file in self.untracked.iter().flatten() {
+            markers.insert(file.clone(), gitutil::UNTRACKED_MARKER.to_string());
+        }
+
+        markers
+    }
+}
 
 /// アプリケーション構造体
-pub struct App;
+pub struct App {
+    /// トークンカウンタ（初めて必要になったときに読み込む）
+    counter: OnceLock<TokenCounter>,
+}
 
 impl Default for App {
     fn default() -> Self {
@@ -20,108 +186,824 @@ impl Default for App {
     }
 }
 
-#[allow(clippy::too_many_arguments)]
 impl App {
     /// 新しいアプリケーションを作成する
     pub fn new() -> Self {
-        App
+        App {
+            counter: OnceLock::new(),
+        }
     }
 
-    /// コマンドを実行する
-    pub fn execute<P: AsRef<Path>>(
-        &self,
-        input_path: P,
-        max_lines: usize,
-        no_tree: bool,
-        no_content: bool,
-        copy_to_clipboard: bool,
-        use_fzf: bool,
-        filter_pattern: Option<String>,
-        show_token_count: bool,
-        exclude_generated: bool,
-    ) -> Result<()> {
-        let mut output = Vec::new();
+    /// 指定されたパス・pathspecを対象にコマンドを実行する
+    pub fn execute<P: AsRef<Path>>(&self, args: &[P], options: &Options) -> Result<()> {
+        let target = self.resolve_target(args)?;
 
-        // ツリービューの表示
-        if !no_tree {
-            let mut writer = &mut output;
-            if let Ok(()) = treeview::tree_view_from_git(&input_path, &mut writer) {
-                writeln!(writer, "\n")?;
+        // 差分モードでは変更されたファイル一覧を最初に取得する
+        let changes = options
+            .diff
+            .as_ref()
+            .map(|spec| gitutil::list_changed_files(&target.pathspec, spec))
+            .transpose()?;
+
+        // リビジョン指定時は作業ツリーの代わりにそのスナップショットを読み込む
+        // （リビジョン範囲の差分では変更後のリビジョンから、ステージ済みの差分ではインデックスから読み込む）
+        let snapshot = match (&options.rev, &options.diff) {
+            (Some(rev), _) => Some(Snapshot::new(&target.pathspec.dir, rev)?),
+            (None, Some(spec)) => spec.snapshot(&target.pathspec.dir)?,
+            (None, None) => None,
+        };
+
+        // 未追跡ファイルを含める場合はGit管理下のファイルと合わせて扱う
+        let untracked = if options.untracked {
+            Some(gitutil::list_untracked_files(&target.pathspec)?)
+        } else {
+            None
+        };
+
+        let mut run = Run {
+            target,
+            changes,
+            snapshot,
+            untracked,
+            encodings: HashMap::new(),
+        };
+
+        // ツリーと内容が一致するように、絞り込み後のファイル一覧からツリーを構築する
+        let (candidates, files, excluded) = self.collect_files(&run, options)?;
+
+        // 作業ツリーのファイルは`.gitattributes`で指定された文字コードで読み込む
+        // （Gitのオブジェクトには変換後のUTF-8で格納されるため、スナップショットでは使わない）
+        if let (Charset::Auto, None, Some(git_root)) =
+            (options.encoding, &run.snapshot, run.git_root())
+        {
+            run.encodings = charset::working_tree_encodings(git_root, &files)?;
+        }
+        let tree = self.build_tree(&run, &candidates, &files, options)?;
+        let files = if options.no_content {
+            Vec::new()
+        } else {
+            files
+        };
+
+        // ファイルごとに標準出力へ書き込む（出力先が閉じられた場合はそこで終了する）
+        let mut sink = OutputSink::new(io::BufWriter::new(io::stdout().lock()), options);
+        let written = if options.format.is_structured() {
+            let tree = tree.as_ref().filter(|_| !options.no_tree);
+            self.write_structured(&run, tree, &files, &mut sink, options)
+        } else {
+            self.write_document(&run, tree.as_ref(), &files, &mut sink, options)
+        };
+        let output = match written.and_then(|_| Ok(sink.finish()?)) {
+            Err(err) if is_broken_pipe(&err) => return Ok(()),
+            result => result?,
+        };
+
+        self.finalize_output(&run, output, &files, options)?;
+
+        // トークン数の多いファイル・ディレクトリの表示（オプションが有効な場合のみ）
+        if let (Some(n), Some(tree)) = (options.top, &tree) {
+            self.print_top(tree, n, &mut self.report_writer(options))?;
+        }
+
+        // 除外したファイルとその理由の表示（オプションが有効な場合のみ）
+        if options.explain_excluded {
+            self.print_excluded(&run, &excluded, &mut self.report_writer(options))?;
+        }
+
+        Ok(())
+    }
+
+    /// コマンドライン引数から対象を決定する
+    fn resolve_target<P: AsRef<Path>>(&self, args: &[P]) -> Result<Target> {
+        let cwd = std::env::current_dir().context("Failed to get current directory")?;
+
+        // 存在するパスは絶対パスとして、それ以外は`:!*.snap`などのpathspecとしてそのまま渡す
+        let mut paths = Vec::new();
+        let mut specs = Vec::new();
+        let mut regions = HashMap::new();
+        for arg in args {
+            let arg = arg.as_ref();
+            match arg.canonicalize() {
+                Ok(path) => {
+                    specs.push(path.clone().into_os_string());
+                    paths.push(path);
+                }
+                Err(_) => match self.parse_region(arg)? {
+                    // 表示範囲付きのファイルは、ファイル自体を対象にする
+                    Some((path, region)) => {
+                        specs.push(path.clone().into_os_string());
+                        regions.insert(path.clone(), region);
+                        paths.push(path);
+                    }
+                    None => specs.push(arg.as_os_str().to_os_string()),
+                },
             }
         }
 
-        // ファイル内容を表示しない場合は終了
-        if no_content {
-            self.finalize_output(&output, copy_to_clipboard, show_token_count)?;
-            return Ok(());
+        // pathspecはカレントディレクトリを基準に解釈する
+        // （カレントディレクトリが対象と別のリポジトリにある場合は最初のパスを基準にする）
+        let dir = match paths.first() {
+            Some(first) => {
+                let first_dir = self.git_dir(first).to_path_buf();
+                match (
+                    gitutil::get_git_root(&cwd),
+                    gitutil::get_git_root(&first_dir),
+                ) {
+                    (Ok(a), Ok(b)) if a == b => cwd.clone(),
+                    _ => first_dir,
+                }
+            }
+            None => cwd.clone(),
+        };
+
+        Ok(Target {
+            root: common_ancestor(&paths).unwrap_or(cwd),
+            paths,
+            git_root: gitutil::get_git_root(&dir).ok(),
+            pathspec: Pathspec::new(dir, specs),
+            regions,
+        })
+    }
+
+    /// `path:40-120`・`path#App::execute`形式の引数をファイルの絶対パスと表示範囲に分ける
+    fn parse_region(&self, arg: &Path) -> Result<Option<(PathBuf, Region)>> {
+        let Some(arg) = arg.to_str() else {
+            return Ok(None);
+        };
+        let Some((path, region)) = Region::parse(arg)? else {
+            return Ok(None);
+        };
+        let Ok(path) = Path::new(path).canonicalize() else {
+            return Ok(None);
+        };
+
+        if !path.is_file() {
+            anyhow::bail!(
+                "Line ranges and symbols can only be used with files: {}",
+                arg
+            );
         }
 
-        // ファイル一覧を取得して内容を表示
-        let path = input_path.as_ref();
+        Ok(Some((path, region)))
+    }
+
+    /// ツリー構造を構築する（必要な場合は各ファイルのトークン数も計算する）
+    fn build_tree(
+        &self,
+        run: &Run,
+        candidates: &[PathBuf],
+        selected: &[PathBuf],
+        options: &Options,
+    ) -> Result<Option<TreeNode>> {
+        if options.no_tree && options.top.is_none() {
+            return Ok(None);
+        }
 
-        if path.is_file() {
-            let mut writer = &mut output;
-            fileview::file_view_with_lines(path, &mut writer, max_lines)?;
+        let files = if options.full_tree {
+            candidates
         } else {
-            let files = self.list_git_files(path)?;
-            let filtered_files = self.filter_files(files, filter_pattern)?;
+            selected
+        };
 
-            // 自動生成ファイルを除外
-            let non_generated_files = if exclude_generated {
-                filefilter::filter_generated_files(filtered_files)?
-            } else {
-                filtered_files
-            };
+        let input_path = &run.target.root;
+        let Ok(mut tree) = treeview::build_tree_from_files(input_path, files, &run.markers())
+        else {
+            return Ok(None);
+        };
+
+        if options.full_tree {
+            let abs_input = input_path
+                .canonicalize()
+                .context("Failed to resolve input path")?;
+            let selected = selected
+                .iter()
+                .map(PathBuf::as_path)
+                .collect::<HashSet<_>>();
+            tree.highlight(&abs_input, &mut |path| selected.contains(path));
+        }
+
+        if options.tree_tokens || options.top.is_some() {
+            let counter = self.token_counter(options.tokenizer)?;
+            let abs_input = input_path
+                .canonicalize()
+                .context("Failed to resolve input path")?;
 
-            let selected_files = if use_fzf && self.is_fzf_installed() {
-                self.select_files_with_fzf(&non_generated_files)?
+            // 読み込めないファイルは0トークンとして扱う
+            tree.annotate_tokens(&abs_input, &mut |path| {
+                self.count_file_tokens(run, path, options, counter)
+                    .unwrap_or(0)
+            });
+        }
+
+        Ok(Some(tree))
+    }
+
+    /// ファイルを出力した場合のトークン数を計算する
+    fn count_file_tokens(
+        &self,
+        run: &Run,
+        path: &Path,
+        options: &Options,
+        counter: &TokenCounter,
+    ) -> Result<usize> {
+        if options.format.is_structured() {
+            return Ok(self.file_record(run, path, options, counter)?.tokens);
+        }
+
+        let (rendered, _) = self.rendered_file(run, path, options)?;
+        Ok(counter.count(&String::from_utf8_lossy(&rendered)))
+    }
+
+    /// ツリーとファイル内容をテキストとして書き込む
+    fn write_document<W: Write>(
+        &self,
+        run: &Run,
+        tree: Option<&TreeNode>,
+        files: &[PathBuf],
+        writer: &mut W,
+        options: &Options,
+    ) -> Result<()> {
+        // ツリービューの表示
+        let mut tree_output = Vec::new();
+        if let Some(tree) = tree.filter(|_| !options.no_tree) {
+            let mut rendered = Vec::new();
+            treeview::print_tree(tree, &mut rendered, options.tree_tokens)?;
+            self.write_tree(&rendered, &mut tree_output, options.format)?;
+            writer.write_all(&tree_output)?;
+            writer.flush()?;
+        }
+
+        // ファイル内容の表示（ツリーのバイト数も出力全体の上限に含める）
+        if !options.no_content {
+            let total = TotalBytes::new(options.max_total_bytes, tree_output.len() as u64);
+            let limited = if let Some(max_tokens) = options.max_tokens {
+                // ツリーで使用したトークン数を差し引いた残りをファイルに割り当てる
+                let counter = self.token_counter(options.tokenizer)?;
+                let budget = max_tokens
+                    .saturating_sub(counter.count(&String::from_utf8_lossy(&tree_output)));
+                self.write_files_within_budget(run, files, writer, options, total, budget)?
             } else {
-                non_generated_files
+                self.write_files(run, files, writer, options, total)?
             };
+            limits::write_limited(writer, &limited, options.format)?;
+        }
+
+        Ok(())
+    }
+
+    /// ツリーとファイル内容をJSON / JSON Linesとして書き込む
+    fn write_structured<W: Write>(
+        &self,
+        run: &Run,
+        tree: Option<&TreeNode>,
+        files: &[PathBuf],
+        writer: &mut W,
+        options: &Options,
+    ) -> Result<()> {
+        let counter = self.token_counter(options.tokenizer)?;
+
+        let load = |file: &PathBuf| self.file_record(run, file, options, counter);
+
+        // 出力全体の上限を超える分のファイルは内容を省略したレコードにする
+        let tree_bytes = match (tree, options.max_total_bytes) {
+            (Some(tree), Some(_)) => serde_json::to_string(tree)?.len() as u64,
+            _ => 0,
+        };
+        let mut total = TotalBytes::new(options.max_total_bytes, tree_bytes);
+        let mut limit = |mut record: FileRecord| {
+            if !total.take(record.content_bytes()) {
+                record.skip();
+            }
+            record
+        };
+
+        // JSON Linesで予算の指定がない場合は、ファイルを読み込んだ順に書き込む
+        if options.format == OutputFormat::Jsonl && options.max_tokens.is_none() {
+            return parallel::map_ordered(files, options.jobs, load, |records| {
+                let records = records.map(|record| record.map(&mut limit));
+                jsonview::write_jsonl(writer, tree, records, &[], counter.encoding())
+            });
+        }
+
+        let records = parallel::map_ordered(files, options.jobs, load, |records| {
+            records.collect::<Result<Vec<_>>>()
+        })?;
+
+        let (records, omitted) = match options.max_tokens {
+            Some(max_tokens) => {
+                let tree_tokens = match tree {
+                    Some(tree) => counter.count(&serde_json::to_string(tree)?),
+                    None => 0,
+                };
+                let candidates = records
+                    .into_iter()
+                    .zip(files)
+                    .map(|(record, path)| Candidate {
+                        path: path.clone(),
+                        rel_path: record.path.clone(),
+                        tokens: record.tokens,
+                        item: record,
+                    })
+                    .collect();
+                let priority = run.priority(options)?;
+                let packing = budget::pack(
+                    candidates,
+                    max_tokens.saturating_sub(tree_tokens),
+                    &priority,
+                );
+                (packing.selected, packing.omitted)
+            }
+            None => (records, Vec::new()),
+        };
+        let records = records.into_iter().map(limit).collect::<Vec<_>>();
+
+        if options.format == OutputFormat::Jsonl {
+            let records = records.into_iter().map(Ok);
+            jsonview::write_jsonl(writer, tree, records, &omitted, counter.encoding())
+        } else {
+            jsonview::write_json(writer, tree, records, &omitted, counter.encoding())
+        }
+    }
+
+    /// ファイルを出力フォーマットに合わせて書き込む（差分モードでは変更内容に応じて書き込む）
+    /// サイズの上限によって内容を省略・短縮した場合はその理由を返す
+    fn render_file<W: Write>(
+        &self,
+        run: &Run,
+        file: &Path,
+        writer: &mut W,
+        options: &Options,
+    ) -> Result<Option<Limited>> {
+        let view_options = self.view_options(run, options, file);
+        let (Some(spec), Some(change)) = (&options.diff, run.change_for(file)) else {
+            let content = fileview::file_view(file, writer, &view_options)?;
+            return Ok(content.limited(&view_options));
+        };
+
+        // 削除されたファイルは内容がないため差分のみを表示する
+        let deleted = change.status == ChangeStatus::Deleted;
+        let mut limited = None;
+        if options.diff_content != DiffContent::Hunks && !deleted {
+            let content = fileview::file_view(file, writer, &view_options)?;
+            limited = content.limited(&view_options);
+        }
+        if options.diff_content != DiffContent::Full || deleted {
+            let diff = gitutil::diff_file(run.diff_root()?, spec, change)?;
+            let diff = options.eol.normalize(&diff);
+            fileview::diff_view(file, change.status.marker(), &diff, writer, &view_options)?;
+        }
+
+        Ok(limited)
+    }
+
+    /// ファイルを出力フォーマットに合わせて整形した内容と、内容を省略・短縮した理由を返す
+    fn rendered_file(
+        &self,
+        run: &Run,
+        file: &Path,
+        options: &Options,
+    ) -> Result<(Vec<u8>, Option<Limited>)> {
+        let mut rendered = Vec::new();
+        let limited = self.render_file(run, file, &mut rendered, options)?;
+        Ok((rendered, limited))
+    }
+
+    /// ファイルのJSONレコードを作成する（差分モードでは変更の種類と差分を含める）
+    fn file_record(
+        &self,
+        run: &Run,
+        file: &Path,
+        options: &Options,
+        counter: &TokenCounter,
+    ) -> Result<FileRecord> {
+        let view_options = self.view_options(run, options, file);
+        let (Some(spec), Some(change)) = (&options.diff, run.change_for(file)) else {
+            return FileRecord::load(file, &view_options, counter);
+        };
+
+        let deleted = change.status == ChangeStatus::Deleted;
+        let record = if deleted {
+            FileRecord::missing(file, &view_options)
+        } else {
+            FileRecord::load(file, &view_options, counter)?
+        };
+
+        let diff = if options.diff_content != DiffContent::Full || deleted {
+            let diff = gitutil::diff_file(run.diff_root()?, spec, change)?;
+            Some(options.eol.normalize(&diff))
+        } else {
+            None
+        };
+
+        Ok(record.with_diff(
+            change.status.marker(),
+            diff,
+            options.diff_content != DiffContent::Hunks,
+            counter,
+        ))
+    }
+
+    /// ファイル表示のオプションを作成する
+    fn view_options(&self, run: &Run, options: &Options, file: &Path) -> ViewOptions {
+        ViewOptions {
+            max_lines: options.max_lines,
+            format: options.format,
+            snapshot: run.snapshot.clone(),
+            lockfiles: options.lockfiles.unwrap_or_default(),
+            region: run.target.regions.get(file).cloned(),
+            truncate: options.truncate,
+            max_file_size: options.max_file_size,
+            max_line_length: options.max_line_length,
+            encoding: run.encodings.get(file).copied().unwrap_or(options.encoding),
+            normalize: self.normalize(options),
+            git_root: run.target.git_root.clone(),
+        }
+    }
+
+    /// 改行・空白の正規化の設定を作成する
+    fn normalize(&self, options: &Options) -> Normalize {
+        Normalize {
+            eol: options.eol,
+            tab_width: options.tab_width,
+            strip_trailing_ws: options.strip_trailing_ws,
+            squeeze_blank: options.squeeze_blank,
+        }
+    }
+
+    /// gitコマンドを実行するディレクトリを返す（ファイルや削除済みのパスは存在する親ディレクトリ）
+    fn git_dir<'a>(&self, path: &'a Path) -> &'a Path {
+        path.ancestors()
+            .find(|p| p.is_dir())
+            .unwrap_or_else(|| Path::new("."))
+    }
+
+    /// 対象となるファイル一覧と、フィルタやfzfで絞り込んだ出力するファイル一覧を取得する
+    /// （`--explain-excluded`指定時は除外したファイルとその理由も返す）
+    fn collect_files(
+        &self,
+        run: &Run,
+        options: &Options,
+    ) -> Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<Excluded>)> {
+        let target = &run.target;
+
+        // ファイルが1つだけ指定された場合はGit管理下かどうかに関わらず表示する
+        if let [path] = target.paths.as_slice() {
+            if target.pathspec.specs.len() == 1 && path.is_file() {
+                let files = vec![path.clone()];
+                return Ok((files.clone(), files, Vec::new()));
+            }
+        }
+
+        let files = match &run.changes {
+            Some(changes) if changes.is_empty() => {
+                anyhow::bail!("No changed files found in: {}", target.root.display());
+            }
+            Some(changes) => changes.iter().map(|c| c.path.clone()).collect(),
+            None => match &run.snapshot {
+                Some(snapshot) => self.list_snapshot_files(snapshot, target)?,
+                None => self.list_git_files(run)?,
+            },
+        };
+
+        // 各段階で取り除かれたファイルを理由とともに記録する
+        let mut excluded = Vec::new();
+        let mut record = |before: &[PathBuf], after: &[PathBuf], reason: &str| {
+            if options.explain_excluded {
+                let kept = after.iter().collect::<HashSet<_>>();
+                excluded.extend(
+                    before
+                        .iter()
+                        .filter(|f| !kept.contains(f))
+                        .map(|f| Excluded::new(f, reason)),
+                );
+            }
+        };
 
-            for file in selected_files {
-                let mut writer = &mut output;
-                fileview::file_view_with_lines(&file, &mut writer, max_lines)?;
+        // .codicatignoreで除外されたファイルはツリーにも表示しない
+        let all_files = files;
+        let files = match (&target.git_root, options.no_ignore_file) {
+            (Some(git_root), false) => {
+                IgnoreFiles::load(git_root, &all_files, run.snapshot.as_ref())?
+                    .filter(all_files.clone())
             }
+            _ => all_files.clone(),
+        };
+        record(&all_files, &files, filefilter::IGNORE_FILE);
+
+        let filtered_files = self.filter_files(files.clone(), options.filter_pattern.clone())?;
+        record(&files, &filtered_files, "--filter");
+        let before = filtered_files;
+        let filtered_files = self.exclude_files(before.clone(), options.exclude_regex.clone())?;
+        record(&before, &filtered_files, "--exclude-regex");
+        let before = filtered_files;
+        let filtered_files = self.filter_globs(before.clone(), target, options)?;
+        record(&before, &filtered_files, "--include/--exclude");
+
+        let (filtered_files, by_attributes) =
+            self.filter_attributes(filtered_files, run, options)?;
+        let (filtered_files, by_lockfiles) = match options.lockfiles {
+            Some(LockfilePolicy::Omit) => filefilter::filter_lockfiles(filtered_files),
+            _ => (filtered_files, Vec::new()),
+        };
+
+        // ルールに基づいて自動生成ファイルを除外
+        let (non_generated_files, by_rules) = if options.exclude_generated {
+            // ロックファイルの扱いが指定された場合はそちらに従う
+            let mut disabled = options.disabled_generated_rules.clone();
+            if options.lockfiles.is_some() {
+                disabled.push("lockfile".to_string());
+            }
+            let rules = RuleSet::new(&options.generated_rules, &disabled)?;
+            let root = target.git_root.as_ref().unwrap_or(&target.root);
+            filefilter::filter_generated_files(
+                filtered_files,
+                root,
+                &rules,
+                run.snapshot.as_ref(),
+                options.jobs,
+            )
+        } else {
+            (filtered_files, Vec::new())
+        };
+
+        if options.explain_excluded {
+            excluded.extend(by_attributes);
+            excluded.extend(by_lockfiles);
+            excluded.extend(by_rules);
+            excluded.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+
+        let selected_files = if options.use_fzf && self.is_fzf_installed() {
+            self.select_files_with_fzf(&non_generated_files)?
+        } else {
+            non_generated_files
+        };
+
+        Ok((files, selected_files, excluded))
+    }
+
+    /// ファイル内容を出力フォーマットに合わせて書き込み、内容を省略・短縮したファイルを返す
+    fn write_files<W: Write>(
+        &self,
+        run: &Run,
+        files: &[PathBuf],
+        writer: &mut W,
+        options: &Options,
+        mut total: TotalBytes,
+    ) -> Result<Vec<Limited>> {
+        if options.format == OutputFormat::Xml {
+            writeln!(writer, "<files>")?;
+        }
+
+        let mut limited = Vec::new();
+        parallel::map_ordered(
+            files,
+            options.jobs,
+            |file| self.rendered_file(run, file, options),
+            |rendered| {
+                for (file, rendered) in files.iter().zip(rendered) {
+                    let (rendered, reason) = rendered?;
+                    if !total.take(rendered.len() as u64) {
+                        limited.push(Limited {
+                            path: run.display_path(file),
+                            reason: total.reason(),
+                        });
+                        continue;
+                    }
+                    limited.extend(reason);
+                    writer.write_all(&rendered)?;
+                    writer.flush()?;
+                }
+                Ok::<_, anyhow::Error>(())
+            },
+        )?;
+
+        if options.format == OutputFormat::Xml {
+            writeln!(writer, "</files>")?;
+        }
+
+        Ok(limited)
+    }
+
+    /// トークン予算に収まるファイルだけを書き込み、省略したファイルを最後に列挙する
+    /// （サイズの上限によって内容を省略・短縮したファイルを返す）
+    fn write_files_within_budget<W: Write>(
+        &self,
+        run: &Run,
+        files: &[PathBuf],
+        writer: &mut W,
+        options: &Options,
+        mut total: TotalBytes,
+        budget: usize,
+    ) -> Result<Vec<Limited>> {
+        let counter = self.token_counter(options.tokenizer)?;
+        let candidates = parallel::map_ordered(
+            files,
+            options.jobs,
+            |file| {
+                let (rendered, reason) = self.rendered_file(run, file, options)?;
+                let rel_path = run.display_path(file);
+                Ok(Candidate {
+                    path: file.clone(),
+                    rel_path: rel_path.clone(),
+                    tokens: counter.count(&String::from_utf8_lossy(&rendered)),
+                    item: (rel_path, rendered, reason),
+                })
+            },
+            |candidates| candidates.collect::<Result<Vec<_>>>(),
+        )?;
+
+        let packing = budget::pack(candidates, budget, &run.priority(options)?);
+
+        if options.format == OutputFormat::Xml {
+            writeln!(writer, "<files>")?;
+        }
+
+        let mut limited = Vec::new();
+        for (path, rendered, reason) in packing.selected {
+            if !total.take(rendered.len() as u64) {
+                limited.push(Limited {
+                    path,
+                    reason: total.reason(),
+                });
+                continue;
+            }
+            limited.extend(reason);
+            writer.write_all(&rendered)?;
+        }
+
+        if options.format == OutputFormat::Xml {
+            writeln!(writer, "</files>")?;
         }
 
-        self.finalize_output(&output, copy_to_clipboard, show_token_count)?;
+        budget::write_omitted(writer, &packing.omitted, options.format)?;
+
+        Ok(limited)
+    }
+
+    /// ツリービューを出力フォーマットに合わせて書き込む
+    fn write_tree<W: Write>(
+        &self,
+        tree: &[u8],
+        writer: &mut W,
+        output_format: OutputFormat,
+    ) -> Result<()> {
+        match output_format {
+            OutputFormat::Plain => {
+                writer.write_all(tree)?;
+                writeln!(writer, "\n")?;
+            }
+            OutputFormat::Markdown => {
+                format::write_fenced_block(writer, "", &String::from_utf8_lossy(tree))?;
+                writeln!(writer)?;
+            }
+            OutputFormat::Xml => {
+                writeln!(writer, "<directory_structure>")?;
+                write!(
+                    writer,
+                    "{}",
+                    format::escape_xml(&String::from_utf8_lossy(tree))
+                )?;
+                writeln!(writer, "</directory_structure>")?;
+            }
+            OutputFormat::Json | OutputFormat::Jsonl => {
+                unreachable!("structured formats are written by write_structured")
+            }
+        }
 
         Ok(())
     }
 
-    /// 出力を標準出力とクリップボードに書き込む
+    /// 保持した出力をクリップボードにコピーし、トークン数を表示する
     fn finalize_output(
         &self,
-        output: &[u8],
-        copy_to_clipboard: bool,
-        show_token_count: bool,
+        run: &Run,
+        output: Option<Vec<u8>>,
+        files: &[PathBuf],
+        options: &Options,
     ) -> Result<()> {
-        if copy_to_clipboard {
-            // クリップボードにコピー
-            self.copy_to_clipboard(String::from_utf8_lossy(output).to_string())?;
-        } else {
-            // クリップボードにコピーしない場合は標準出力に表示
-            let stdout = io::stdout();
-            let mut stdout_handle = stdout.lock();
-            stdout_handle.write_all(output)?;
+        let output = output.unwrap_or_default();
+
+        // クリップボードにコピー（この場合は標準出力には書き込んでいない）
+        if options.copy_to_clipboard {
+            self.copy_to_clipboard(String::from_utf8_lossy(&output).to_string())?;
         }
 
         // トークン数情報の表示（オプションが有効な場合のみ）
-        if show_token_count {
-            let token_count = self.count_tokens(&String::from_utf8_lossy(output))?;
-            println!("Token count: {}", token_count);
+        if options.show_token_count {
+            let token_count =
+                self.count_tokens(&String::from_utf8_lossy(&output), options.tokenizer)?;
+            let mut report = self.report_writer(options);
+            writeln!(
+                report,
+                "Token count: {} ({})",
+                token_count,
+                options.tokenizer.name()
+            )?;
+
+            for (label, saved) in self.normalization_savings(run, files, options)? {
+                // タブの展開などでトークン数が増えた場合は、負の削減量ではなく増加量として表示する
+                if saved < 0 {
+                    writeln!(report, "Added by {}: {} tokens", label, -saved)?;
+                } else {
+                    writeln!(report, "Saved by {}: {} tokens", label, saved)?;
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Git管理下のファイル一覧を取得する
-    fn list_git_files<P: AsRef<Path>>(&self, path: P) -> Result<Vec<PathBuf>> {
-        match gitutil::list_git_tracked_files(path.as_ref()) {
-            Ok(files) => {
+    /// 出力の後に表示する情報の書き込み先を返す
+    /// （JSON / JSON Linesでは標準出力を解析できるよう、文書の後に書き足さずに標準エラー出力に書き込む）
+    fn report_writer(&self, options: &Options) -> Box<dyn Write> {
+        if options.format.is_structured() {
+            Box::new(io::stderr())
+        } else {
+            Box::new(io::stdout())
+        }
+    }
+
+    /// 正規化のオプションごとに、そのオプションだけを無効にした場合と比べて削減したトークン数を求める
+    fn normalization_savings(
+        &self,
+        run: &Run,
+        files: &[PathBuf],
+        options: &Options,
+    ) -> Result<Vec<(String, i64)>> {
+        let counter = self.token_counter(options.tokenizer)?;
+        let normalize = self.normalize(options);
+        let variants = normalize.each_enabled();
+        if variants.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // 削除されたファイルは内容がないため対象にしない
+        let files = files
+            .iter()
+            .filter(|f| {
+                run.change_for(f)
+                    .is_none_or(|c| c.status != ChangeStatus::Deleted)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let tokens = |file: &PathBuf, normalize: Normalize| -> Result<i64> {
+            let view_options = ViewOptions {
+                normalize,
+                ..self.view_options(run, options, file)
+            };
+            // トークン数の表示と同じく、出力フォーマットに整形した内容で比べる
+            let text = if options.format.is_structured() {
+                fileview::load_file(file, &view_options)?.text()
+            } else {
+                let mut rendered = Vec::new();
+                fileview::file_view(file, &mut rendered, &view_options)?;
+                String::from_utf8_lossy(&rendered).into_owned()
+            };
+            Ok(counter.count(&text) as i64)
+        };
+
+        let saved = parallel::map_ordered(
+            &files,
+            options.jobs,
+            |file| {
+                let normalized = tokens(file, normalize)?;
+                variants
+                    .iter()
+                    .map(|(_, without)| Ok(tokens(file, *without)? - normalized))
+                    .collect::<Result<Vec<_>>>()
+            },
+            |saved| {
+                let mut total = vec![0; variants.len()];
+                for file in saved {
+                    for (total, saved) in total.iter_mut().zip(file?) {
+                        *total += saved;
+                    }
+                }
+                Ok::<_, anyhow::Error>(total)
+            },
+        )?;
+
+        Ok(variants
+            .into_iter()
+            .map(|(label, _)| label)
+            .zip(saved)
+            .collect())
+    }
+
+    /// 対象のGit管理下のファイル一覧を取得する（`--untracked`指定時は未追跡ファイルも含める）
+    fn list_git_files(&self, run: &Run) -> Result<Vec<PathBuf>> {
+        let target = &run.target;
+        match gitutil::list_git_tracked_files_in(&target.pathspec) {
+            Ok(mut files) => {
+                if let Some(untracked) = &run.untracked {
+                    files.extend(untracked.iter().cloned());
+                    files.sort();
+                    files.dedup();
+                }
                 if files.is_empty() {
-                    anyhow::bail!("No Git-tracked files found in: {}", path.as_ref().display());
+                    anyhow::bail!("No Git-tracked files found in: {}", target.root.display());
                 }
                 Ok(files)
             }
@@ -129,7 +1011,7 @@ impl App {
                 if err.to_string().contains("Not a Git repository") {
                     anyhow::bail!(
                         "This directory is not inside a Git repository: {}",
-                        path.as_ref().display()
+                        target.pathspec.dir.display()
                     );
                 }
                 anyhow::bail!("Failed to list files: {}", err);
@@ -137,6 +1019,21 @@ impl App {
         }
     }
 
+    /// スナップショットに含まれる対象のファイル一覧を取得する
+    fn list_snapshot_files(&self, snapshot: &Snapshot, target: &Target) -> Result<Vec<PathBuf>> {
+        let files = snapshot.list_files(&target.pathspec)?;
+
+        if files.is_empty() {
+            anyhow::bail!(
+                "No files found at revision {} in: {}",
+                snapshot.rev,
+                target.root.display()
+            );
+        }
+
+        Ok(files)
+    }
+
     /// 正規表現パターンに基づいてファイルをフィルタリングする
     fn filter_files(&self, files: Vec<PathBuf>, pattern: Option<String>) -> Result<Vec<PathBuf>> {
         if let Some(pattern) = pattern {
@@ -156,6 +1053,62 @@ impl App {
         }
     }
 
+    /// 正規表現パターンにマッチするファイルを除外する
+    fn exclude_files(&self, files: Vec<PathBuf>, pattern: Option<String>) -> Result<Vec<PathBuf>> {
+        let Some(pattern) = pattern else {
+            return Ok(files);
+        };
+
+        let re = Regex::new(&pattern).context("Invalid exclude regex pattern")?;
+
+        Ok(files
+            .into_iter()
+            .filter(|f| !re.is_match(&f.to_string_lossy()))
+            .collect())
+    }
+
+    /// `--include` / `--exclude`のglobパターンに基づいてファイルをフィルタリングする
+    fn filter_globs(
+        &self,
+        files: Vec<PathBuf>,
+        target: &Target,
+        options: &Options,
+    ) -> Result<Vec<PathBuf>> {
+        if options.include_globs.is_empty() && options.exclude_globs.is_empty() {
+            return Ok(files);
+        }
+
+        // パターンはGitリポジトリのルートからの相対パスにマッチさせる
+        let git_root = target
+            .git_root
+            .as_ref()
+            .context("Glob patterns require a Git repository")?;
+        let filter = GlobFilter::new(git_root, &options.include_globs, &options.exclude_globs)?;
+
+        Ok(files.into_iter().filter(|f| filter.is_match(f)).collect())
+    }
+
+    /// `.gitattributes`の属性（`linguist-generated`など）に基づいてファイルを除外する
+    fn filter_attributes(
+        &self,
+        files: Vec<PathBuf>,
+        run: &Run,
+        options: &Options,
+    ) -> Result<(Vec<PathBuf>, Vec<Excluded>)> {
+        let filter = AttributeFilter {
+            generated: options.exclude_generated,
+            vendored: options.exclude_vendored,
+            documentation: options.exclude_docs,
+        };
+
+        match run.git_root() {
+            Some(git_root) if !filter.is_empty() => {
+                filter.filter(git_root, files, run.snapshot.as_ref())
+            }
+            _ => Ok((files, Vec::new())),
+        }
+    }
+
     /// fzfがインストールされているかチェックする
     fn is_fzf_installed(&self) -> bool {
         Command::new("which")
@@ -214,10 +1167,128 @@ impl App {
         Ok(())
     }
 
+    /// トークン数の多いファイルとディレクトリを表示する
+    fn print_top<W: Write>(&self, tree: &TreeNode, n: usize, writer: &mut W) -> Result<()> {
+        let (files, dirs) = tree.heaviest(n);
+
+        writeln!(writer, "Top {} files by tokens:", n)?;
+        for (path, tokens) in files {
+            writeln!(writer, "{:>8}  {}", tokens, path)?;
+        }
+
+        writeln!(writer, "Top {} directories by tokens:", n)?;
+        for (path, tokens) in dirs {
+            writeln!(writer, "{:>8}  {}", tokens, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// 除外したファイルとその理由を表示する
+    fn print_excluded<W: Write>(
+        &self,
+        run: &Run,
+        excluded: &[Excluded],
+        writer: &mut W,
+    ) -> Result<()> {
+        writeln!(writer, "Excluded files: {}", excluded.len())?;
+        for file in excluded {
+            writeln!(
+                writer,
+                "/{}  ({})",
+                run.display_path(&file.path),
+                file.reason
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// トークンカウンタを取得する（初回のみエンコーディングを読み込む）
+    fn token_counter(&self, encoding: Encoding) -> Result<&TokenCounter> {
+        if let Some(counter) = self.counter.get() {
+            return Ok(counter);
+        }
+
+        let counter = TokenCounter::new(encoding)?;
+        Ok(self.counter.get_or_init(|| counter))
+    }
+
     /// テキストのトークン数を計算する
-    fn count_tokens(&self, text: &str) -> Result<usize> {
-        let bpe = cl100k_base().context("Failed to load cl100k_base encoding")?;
-        let tokens = bpe.encode_with_special_tokens(text);
-        Ok(tokens.len())
+    fn count_tokens(&self, text: &str, encoding: Encoding) -> Result<usize> {
+        Ok(self.token_counter(encoding)?.count(text))
+    }
+}
+
+/// 出力先（標準出力に逐次書き込み、クリップボードへのコピーやトークン数の計算に必要な場合のみ内容を保持する）
+struct OutputSink<W: Write> {
+    /// 逐次書き込む出力先（クリップボードにコピーする場合はなし）
+    writer: Option<W>,
+    /// 書き込んだ内容（コピーもトークン数の表示も不要な場合はなし）
+    buffer: Option<Vec<u8>>,
+}
+
+impl<W: Write> OutputSink<W> {
+    /// オプションに合わせて出力先を作成する
+    fn new(writer: W, options: &Options) -> Self {
+        Self {
+            writer: (!options.copy_to_clipboard).then_some(writer),
+            buffer: (options.copy_to_clipboard || options.show_token_count).then(Vec::new),
+        }
+    }
+
+    /// 出力先をフラッシュし、保持した内容を返す
+    fn finish(mut self) -> io::Result<Option<Vec<u8>>> {
+        self.flush()?;
+        Ok(self.buffer)
     }
 }
+
+impl<W: Write> Write for OutputSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = match &mut self.writer {
+            Some(writer) => writer.write(buf)?,
+            None => buf.len(),
+        };
+        if let Some(buffer) = &mut self.buffer {
+            buffer.extend_from_slice(&buf[..written]);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.writer {
+            Some(writer) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 出力先（パイプの読み手）が閉じられたことによるエラーかどうか
+fn is_broken_pipe(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let kind = match cause.downcast_ref::<io::Error>() {
+            Some(err) => Some(err.kind()),
+            None => cause
+                .downcast_ref::<serde_json::Error>()
+                .and_then(serde_json::Error::io_error_kind),
+        };
+        kind == Some(io::ErrorKind::BrokenPipe)
+    })
+}
+
+/// パスの共通の親を返す（パスが1つの場合はそのパス自身）
+fn common_ancestor(paths: &[PathBuf]) -> Option<PathBuf> {
+    let (first, rest) = paths.split_first()?;
+    let mut common = first.clone();
+
+    for path in rest {
+        while !path.starts_with(&common) {
+            if !common.pop() {
+                return None;
+            }
+        }
+    }
+
+    Some(common)
+}
@@ -0,0 +1,176 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::charset::Charset;
+use crate::fileview::{DiffContent, Eol, LockfilePolicy, Truncate};
+use crate::format::OutputFormat;
+use crate::generated::RuleConfig;
+use crate::gitutil;
+use crate::limits::ByteSize;
+use crate::tokens::Encoding;
+
+/// Gitリポジトリのルートに置くプロジェクトの設定ファイル名
+pub const PROJECT_CONFIG_FILE: &str = ".codicat.toml";
+
+/// 設定項目の一覧から、設定の構造体と上書きの処理を生成する
+/// （各項目は`Option`で保持し、指定されなかった項目はNoneとして出力しない）
+macro_rules! settings {
+    ($($(#[$attr:meta])* $field:ident: $ty:ty,)*) => {
+        /// 設定ファイルで指定できる項目（各項目は同名のコマンドラインオプションに対応し、指定されなかった項目はNone）
+        #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+        #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+        pub struct Settings {
+            $(
+                $(#[$attr])*
+                #[serde(skip_serializing_if = "Option::is_none")]
+                pub $field: Option<$ty>,
+            )*
+        }
+
+        impl Settings {
+            /// otherで指定された項目で上書きした設定を返す
+            pub fn merge(self, other: Settings) -> Settings {
+                Settings {
+                    $($field: other.$field.or(self.$field),)*
+                }
+            }
+        }
+    };
+}
+
+settings! {
+    max_lines: usize,
+    truncate: Truncate,
+    max_file_size: ByteSize,
+    max_line_length: usize,
+    max_total_bytes: ByteSize,
+    encoding: Charset,
+    eol: Eol,
+    tab_width: usize,
+    strip_trailing_ws: bool,
+    squeeze_blank: bool,
+    no_tree: bool,
+    no_content: bool,
+    copy: bool,
+    fzf: bool,
+    filter: String,
+    exclude_regex: String,
+    #[serde(rename = "include")]
+    include_globs: Vec<String>,
+    #[serde(rename = "exclude")]
+    exclude_globs: Vec<String>,
+    no_ignore_file: bool,
+    token_count: bool,
+    exclude_generated: bool,
+    exclude_vendored: bool,
+    exclude_docs: bool,
+    explain_excluded: bool,
+    lockfiles: LockfilePolicy,
+    format: OutputFormat,
+    max_tokens: usize,
+    #[serde(rename = "priority")]
+    priority_globs: Vec<String>,
+    tree_tokens: bool,
+    top: usize,
+    tokenizer: Encoding,
+    diff_content: DiffContent,
+    untracked: bool,
+    full_tree: bool,
+    jobs: usize,
+    /// 無効にする自動生成ファイルのルール名（設定ファイルのみ）
+    disable_generated_rules: Vec<String>,
+    /// 追加する自動生成ファイルのルール（設定ファイルのみ、`[[generated-rule]]`）
+    #[serde(rename = "generated-rule")]
+    generated_rules: Vec<RuleConfig>,
+}
+
+/// 1つの設定ファイルの内容（既定の設定と名前付きプロファイル）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct ConfigFile {
+    /// 常に適用する設定
+    #[serde(flatten)]
+    defaults: Settings,
+    /// `--profile`で選択する設定
+    profile: BTreeMap<String, Settings>,
+}
+
+/// 読み込んだ設定ファイル（後ろのものほど優先される）
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// 読み込んだファイルのパスと内容
+    files: Vec<(PathBuf, ConfigFile)>,
+}
+
+impl Config {
+    /// ユーザー設定（`~/.config/codicat/config.toml`）とプロジェクト設定（Gitルートの`.codicat.toml`）を読み込む
+    pub fn load<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut candidates = Vec::new();
+        if let Some(path) = user_config_path() {
+            candidates.push(path);
+        }
+        if let Ok(git_root) = gitutil::get_git_root(dir.as_ref()) {
+            candidates.push(git_root.join(PROJECT_CONFIG_FILE));
+        }
+
+        let mut config = Config::default();
+        for path in candidates.into_iter().filter(|p| p.is_file()) {
+            let text = std::fs::read_to_string(&path)
+                .context(format!("Failed to read config file: {}", path.display()))?;
+            let file = toml::from_str(&text)
+                .context(format!("Invalid config file: {}", path.display()))?;
+            config.files.push((path, file));
+        }
+
+        Ok(config)
+    }
+
+    /// 読み込んだ設定ファイルのパス一覧
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(path, _)| path.as_path())
+    }
+
+    /// 既定の設定に指定されたプロファイルを重ねた設定を返す
+    pub fn settings(&self, profile: Option<&str>) -> Result<Settings> {
+        let mut settings = self
+            .files
+            .iter()
+            .fold(Settings::default(), |acc, (_, file)| {
+                acc.merge(file.defaults.clone())
+            });
+
+        if let Some(name) = profile {
+            let profiles = self
+                .files
+                .iter()
+                .filter_map(|(_, file)| file.profile.get(name))
+                .collect::<Vec<_>>();
+
+            if profiles.is_empty() {
+                anyhow::bail!("Unknown profile: {}", name);
+            }
+
+            for profile in profiles {
+                settings = settings.merge(profile.clone());
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+/// ユーザー設定ファイルのパスを返す（`$XDG_CONFIG_HOME`、なければ`~/.config`の下）
+fn user_config_path() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+
+    Some(config_dir.join("codicat").join("config.toml"))
+}
@@ -1,42 +1,435 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
-/// 自動生成ファイル検出用の定数
-const HEADER_CHECK_LINES: usize = 10;
-const HEADER_KEYWORDS: [&str; 4] = [
-    "generated by",
-    "do not edit",
-    "automatically generated",
-    "auto-generated",
+use crate::generated::{self, RuleSet};
+use crate::gitutil::{self, Snapshot};
+use crate::parallel;
+
+/// 出力から除外するファイルを指定するファイル名（gitignoreと同じ書式）
+pub const IGNORE_FILE: &str = ".codicatignore";
+
+/// ロックファイルとして扱うファイル名
+pub const LOCKFILE_NAMES: &[&str] = &[
+    "Cargo.lock",
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lockb",
+    "bun.lock",
+    "poetry.lock",
+    "Pipfile.lock",
+    "uv.lock",
+    "pdm.lock",
+    "composer.lock",
+    "Gemfile.lock",
+    "go.sum",
+    "flake.lock",
+    "mix.lock",
+    "pubspec.lock",
+    "Podfile.lock",
+    "Package.resolved",
+    "packages.lock.json",
+    "gradle.lockfile",
 ];
 
-/// 自動生成ファイルをフィルタリングする
-pub fn filter_generated_files(files: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
-    let filtered = files
+/// ロックファイルの形式（要約するときの解析方法に対応する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lockfile {
+    /// `[[package]]`の配列を持つTOML（Cargo.lock・poetry.lock・uv.lock・pdm.lock）
+    TomlPackages,
+    /// package-lock.json・npm-shrinkwrap.json
+    Npm,
+    /// yarn.lock（v1とBerryの両方）
+    Yarn,
+    /// pnpm-lock.yaml
+    Pnpm,
+    /// Pipfile.lock
+    Pipfile,
+    /// composer.lock
+    Composer,
+    /// Gemfile.lock
+    Gemfile,
+    /// go.sum
+    GoSum,
+    /// 要約に対応していないロックファイル
+    Other,
+}
+
+impl Lockfile {
+    /// ファイル名からロックファイルの形式を判定する（ロックファイルでない場合はNone）
+    pub fn detect(path: &Path) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+
+        let kind = match file_name {
+            "Cargo.lock" | "poetry.lock" | "uv.lock" | "pdm.lock" => Lockfile::TomlPackages,
+            "package-lock.json" | "npm-shrinkwrap.json" => Lockfile::Npm,
+            "yarn.lock" => Lockfile::Yarn,
+            "pnpm-lock.yaml" => Lockfile::Pnpm,
+            "Pipfile.lock" => Lockfile::Pipfile,
+            "composer.lock" => Lockfile::Composer,
+            "Gemfile.lock" => Lockfile::Gemfile,
+            "go.sum" => Lockfile::GoSum,
+            name if LOCKFILE_NAMES.contains(&name) => Lockfile::Other,
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+/// ロックファイルを取り除き、残ったファイルと除外したファイルを返す
+pub fn filter_lockfiles(files: Vec<PathBuf>) -> (Vec<PathBuf>, Vec<Excluded>) {
+    let (lockfiles, kept): (Vec<_>, Vec<_>) = files
         .into_iter()
-        .filter(|f| match is_auto_generated(f) {
-            Ok(is_generated) => !is_generated,
-            Err(_) => true, // エラーの場合はファイルを含める
-        })
+        .partition(|f| Lockfile::detect(f).is_some());
+
+    let excluded = lockfiles
+        .into_iter()
+        .map(|f| Excluded::new(f, "--lockfiles=omit"))
         .collect();
 
-    Ok(filtered)
+    (kept, excluded)
 }
 
-/// ファイルが自動生成されたものかを判断する
+/// 出力対象から除外されたファイルとその理由
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Excluded {
+    /// 除外されたファイルのパス
+    pub path: PathBuf,
+    /// 除外した理由（マッチしたルールやオプション）
+    pub reason: String,
+}
+
+impl Excluded {
+    /// 除外されたファイルと理由を作成する
+    pub fn new<P: Into<PathBuf>, S: Into<String>>(path: P, reason: S) -> Self {
+        Self {
+            path: path.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// 自動生成ファイルをルールに基づいて取り除き、残ったファイルと除外したファイルを返す
+/// （スナップショット指定時はそのリビジョンの内容で判定する）
+pub fn filter_generated_files<P: AsRef<Path>>(
+    files: Vec<PathBuf>,
+    root: P,
+    rules: &RuleSet,
+    snapshot: Option<&Snapshot>,
+    jobs: usize,
+) -> (Vec<PathBuf>, Vec<Excluded>) {
+    let root = root.as_ref();
+
+    // 先頭部分の読み込みと判定はファイルごとに並列に行う
+    let rules_found = parallel::map_ordered(
+        &files,
+        jobs,
+        |file| {
+            let rel_path = file.strip_prefix(root).unwrap_or(file);
+            rules
+                .find(rel_path, || match snapshot {
+                    Some(snapshot) => snapshot.read(file).ok().map(|mut bytes| {
+                        bytes.truncate(generated::HEADER_BYTES);
+                        bytes
+                    }),
+                    // 読み込めない場合はファイルを含める
+                    None => read_header(file).ok(),
+                })
+                .map(str::to_string)
+        },
+        |results| results.collect::<Vec<_>>(),
+    );
+
+    let mut kept = Vec::new();
+    let mut excluded = Vec::new();
+
+    for (file, rule) in files.into_iter().zip(rules_found) {
+        match rule {
+            Some(rule) => excluded.push(Excluded::new(&file, format!("generated: {}", rule))),
+            None => kept.push(file),
+        }
+    }
+
+    (kept, excluded)
+}
+
+/// ファイルが自動生成されたものかを組み込みのルールで判断する
 pub fn is_auto_generated(path: &Path) -> io::Result<bool> {
-    if let Ok(file) = File::open(path) {
-        let reader = BufReader::new(file);
-        for line in reader.lines().take(HEADER_CHECK_LINES).flatten() {
-            let lower_line = line.to_lowercase();
-            for keyword in HEADER_KEYWORDS.iter() {
-                if lower_line.contains(keyword) {
-                    return Ok(true);
+    let rules = RuleSet::default();
+    Ok(rules.find(path, || read_header(path).ok()).is_some())
+}
+
+/// ファイルの先頭部分を読み込む
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    let mut header = Vec::new();
+    File::open(path)?
+        .take(generated::HEADER_BYTES as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// `.gitattributes`の属性で除外するファイルの種類
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeFilter {
+    /// `linguist-generated`・`-diff`・`binary`が指定されたファイルを除外する
+    pub generated: bool,
+    /// `linguist-vendored`が指定されたファイルを除外する
+    pub vendored: bool,
+    /// `linguist-documentation`が指定されたファイルを除外する
+    pub documentation: bool,
+}
+
+impl AttributeFilter {
+    /// 除外する種類が1つも指定されていないかどうか
+    pub fn is_empty(&self) -> bool {
+        !(self.generated || self.vendored || self.documentation)
+    }
+
+    /// 確認が必要な属性名
+    fn attribute_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        if self.generated {
+            names.extend(["linguist-generated", "diff", "binary"]);
+        }
+        if self.vendored {
+            names.push("linguist-vendored");
+        }
+        if self.documentation {
+            names.push("linguist-documentation");
+        }
+        names
+    }
+
+    /// 属性で除外されるファイルを取り除き、残ったファイルと除外したファイルを返す
+    /// （属性は作業ツリー、スナップショットを指定した場合はそのリビジョンの`.gitattributes`から取得する）
+    pub fn filter<P: AsRef<Path>>(
+        &self,
+        git_root: P,
+        files: Vec<PathBuf>,
+        snapshot: Option<&Snapshot>,
+    ) -> Result<(Vec<PathBuf>, Vec<Excluded>)> {
+        if self.is_empty() {
+            return Ok((files, Vec::new()));
+        }
+
+        let attributes = gitutil::check_attributes(
+            git_root.as_ref(),
+            &files,
+            &self.attribute_names(),
+            snapshot,
+        )?;
+
+        let mut kept = Vec::new();
+        let mut excluded = Vec::new();
+        for file in files {
+            match attributes.get(&file).and_then(|a| self.excluded_by(a)) {
+                Some(attribute) => excluded.push(Excluded::new(
+                    &file,
+                    format!("gitattributes: {}", attribute),
+                )),
+                None => kept.push(file),
+            }
+        }
+
+        Ok((kept, excluded))
+    }
+
+    /// ファイルを除外する理由となった属性を返す
+    fn excluded_by(&self, attributes: &HashMap<String, String>) -> Option<&'static str> {
+        let value = |name: &str| attributes.get(name).map(String::as_str);
+        let is_true = |name: &str| matches!(value(name), Some("set" | "true"));
+
+        if self.generated {
+            if is_true("linguist-generated") {
+                return Some("linguist-generated");
+            }
+            if value("binary") == Some("set") {
+                return Some("binary");
+            }
+            if value("diff") == Some("unset") {
+                return Some("-diff");
+            }
+        }
+        if self.vendored && is_true("linguist-vendored") {
+            return Some("linguist-vendored");
+        }
+        if self.documentation && is_true("linguist-documentation") {
+            return Some("linguist-documentation");
+        }
+
+        None
+    }
+}
+
+/// `--include` / `--exclude`のglobパターン（gitignoreと同じ書式）でファイルを絞り込むフィルタ
+pub struct GlobFilter {
+    /// パターンの基準となるGitリポジトリのルート
+    root: PathBuf,
+    /// 含めるファイルのパターン（なしの場合はすべて含める）
+    include: Option<Gitignore>,
+    /// 除外するファイルのパターン
+    exclude: Option<Gitignore>,
+}
+
+impl GlobFilter {
+    /// Gitリポジトリのルートからの相対パスにマッチさせるフィルタを作成する
+    pub fn new<P: AsRef<Path>>(root: P, include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(Self {
+            root: root.as_ref().to_path_buf(),
+            include: build_matcher(root.as_ref(), include)?,
+            exclude: build_matcher(root.as_ref(), exclude)?,
+        })
+    }
+
+    /// ファイルが出力対象かどうか
+    pub fn is_match(&self, path: &Path) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|include| self.matches(include, path));
+        let excluded = self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| self.matches(exclude, path));
+
+        included && !excluded
+    }
+
+    /// パターンにマッチするかどうか（親ディレクトリへのマッチや`!`による打ち消しも考慮する）
+    fn matches(&self, matcher: &Gitignore, path: &Path) -> bool {
+        match path.strip_prefix(&self.root) {
+            Ok(rel_path) => matcher
+                .matched_path_or_any_parents(rel_path, false)
+                .is_ignore(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// gitignore形式のパターンからマッチャーを作成する（パターンがない場合はNone）
+fn build_matcher(root: &Path, patterns: &[String]) -> Result<Option<Gitignore>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .context(format!("Invalid glob pattern: {}", pattern))?;
+    }
+
+    Ok(Some(
+        builder.build().context("Failed to build glob patterns")?,
+    ))
+}
+
+/// スナップショットから読み込んだ`.codicatignore`の内容からマッチャーを作成する
+fn matcher_from_content(dir: &Path, ignore_file: &Path, content: &[u8]) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    for line in String::from_utf8_lossy(content).lines() {
+        builder
+            .add_line(Some(ignore_file.to_path_buf()), line)
+            .context(format!("Invalid {}", ignore_file.display()))?;
+    }
+
+    builder
+        .build()
+        .context(format!("Invalid {}", ignore_file.display()))
+}
+
+/// ディレクトリごとに配置された`.codicatignore`で除外するファイルを判定する
+pub struct IgnoreFiles {
+    /// `.codicatignore`があるディレクトリとそのマッチャー
+    matchers: HashMap<PathBuf, Gitignore>,
+}
+
+impl IgnoreFiles {
+    /// Gitリポジトリのルートからファイルまでの各ディレクトリにある`.codicatignore`を読み込む
+    /// （スナップショットを指定した場合は作業ツリーではなく、そのリビジョンに含まれるものを読み込む）
+    pub fn load<P: AsRef<Path>>(
+        root: P,
+        files: &[PathBuf],
+        snapshot: Option<&Snapshot>,
+    ) -> Result<Self> {
+        let root = root.as_ref();
+        let mut matchers = HashMap::new();
+        let mut visited = HashSet::new();
+
+        let in_snapshot = match snapshot {
+            Some(snapshot) => Some(
+                snapshot
+                    .files()?
+                    .into_iter()
+                    .filter(|f| f.file_name().is_some_and(|name| name == IGNORE_FILE))
+                    .collect::<HashSet<_>>(),
+            ),
+            None => None,
+        };
+
+        for file in files {
+            for dir in file.ancestors().skip(1) {
+                if !dir.starts_with(root) || !visited.insert(dir.to_path_buf()) {
+                    break;
+                }
+
+                let ignore_file = dir.join(IGNORE_FILE);
+                match (snapshot, &in_snapshot) {
+                    (Some(snapshot), Some(in_snapshot)) => {
+                        if in_snapshot.contains(&ignore_file) {
+                            let content = snapshot.read(&ignore_file)?;
+                            let matcher = matcher_from_content(dir, &ignore_file, &content)?;
+                            matchers.insert(dir.to_path_buf(), matcher);
+                        }
+                    }
+                    _ => {
+                        if ignore_file.is_file() {
+                            let (matcher, err) = Gitignore::new(&ignore_file);
+                            if let Some(err) = err {
+                                return Err(err)
+                                    .context(format!("Invalid {}", ignore_file.display()));
+                            }
+                            matchers.insert(dir.to_path_buf(), matcher);
+                        }
+                    }
                 }
             }
         }
+
+        Ok(Self { matchers })
+    }
+
+    /// ファイルが除外対象かどうか（より深い階層の`.codicatignore`が優先される）
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        for dir in path.ancestors().skip(1) {
+            let Some(matcher) = self.matchers.get(dir) else {
+                continue;
+            };
+
+            let matched = matcher.matched_path_or_any_parents(path, false);
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// `.codicatignore`で除外されるファイルを取り除く
+    pub fn filter(&self, files: Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.matchers.is_empty() {
+            return files;
+        }
+
+        files.into_iter().filter(|f| !self.is_ignored(f)).collect()
     }
-    Ok(false)
 }
@@ -1,9 +1,385 @@
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufRead, BufReader, Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::gitutil;
+use crate::charset::{self, Charset};
+use crate::filefilter::Lockfile;
+use crate::format::{self, OutputFormat};
+use crate::gitutil::Snapshot;
+use crate::limits;
+use crate::region::Region;
+
+/// バイナリ判定で確認する先頭のバイト数
+const BINARY_CHECK_BYTES: usize = 8000;
+
+/// ファイル表示のオプション
+#[derive(Debug, Clone, Default)]
+pub struct ViewOptions {
+    /// ファイルごとの最大表示行数（0の場合は無制限）
+    pub max_lines: usize,
+    /// 出力フォーマット
+    pub format: OutputFormat,
+    /// 読み込み元のスナップショット（なしの場合は作業ツリーから読み込む）
+    pub snapshot: Option<Snapshot>,
+    /// ロックファイルの表示方法
+    pub lockfiles: LockfilePolicy,
+    /// 表示する行範囲・シンボル（なしの場合はファイル全体）
+    pub region: Option<Region>,
+    /// max_linesを超えるファイルの切り詰め方
+    pub truncate: Truncate,
+    /// 内容を表示するファイルの最大サイズ（バイト、超える場合は内容を省略する）
+    pub max_file_size: Option<u64>,
+    /// 1行の最大文字数（超える部分はマーカーに置き換える）
+    pub max_line_length: Option<usize>,
+    /// ファイルの文字コード（UTF-8以外はUTF-8に変換して出力する）
+    pub encoding: Charset,
+    /// 改行・空白の正規化
+    pub normalize: Normalize,
+    /// Gitリポジトリのルート（出力するパスはここからの相対パスにする。なしの場合はカレントディレクトリから）
+    pub git_root: Option<PathBuf>,
+}
+
+impl ViewOptions {
+    /// 作業ツリーのファイルを指定されたフォーマットで表示するオプションを作成する
+    pub fn new(max_lines: usize, format: OutputFormat) -> Self {
+        Self {
+            max_lines,
+            format,
+            snapshot: None,
+            lockfiles: LockfilePolicy::Full,
+            region: None,
+            truncate: Truncate::Head,
+            max_file_size: None,
+            max_line_length: None,
+            encoding: Charset::Auto,
+            normalize: Normalize::default(),
+            git_root: None,
+        }
+    }
+}
+
+/// 差分モードで表示する内容
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffContent {
+    /// Full contents of the changed files
+    #[default]
+    Full,
+    /// Unified diff hunks instead of the contents
+    Hunks,
+    /// Full contents followed by the diff hunks
+    Both,
+}
+
+/// ロックファイルの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockfilePolicy {
+    /// Leave lockfiles out of the output
+    Omit,
+    /// Replace the contents with a package@version list
+    Summary,
+    /// Print the full contents
+    #[default]
+    Full,
+}
+
+/// max_linesを超えるファイルの切り詰め方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Truncate {
+    /// Keep the first N lines
+    #[default]
+    Head,
+    /// Keep the last N lines
+    Tail,
+    /// Keep the first and last N/2 lines, eliding the middle
+    HeadTail,
+    /// Keep the N lines in the middle, eliding both ends
+    Middle,
+}
+
+impl Truncate {
+    /// len行のうち省略する行の範囲を返す（0始まりの開始位置と終了位置）
+    fn omitted(self, len: usize, max_lines: usize) -> Vec<(usize, usize)> {
+        if max_lines == 0 || len <= max_lines {
+            return Vec::new();
+        }
+
+        let ranges = match self {
+            Truncate::Head => vec![(max_lines, len)],
+            Truncate::Tail => vec![(0, len - max_lines)],
+            Truncate::HeadTail => {
+                let head = max_lines.div_ceil(2);
+                vec![(head, len - (max_lines - head))]
+            }
+            Truncate::Middle => {
+                let start = (len - max_lines) / 2;
+                vec![(0, start), (start + max_lines, len)]
+            }
+        };
+
+        ranges
+            .into_iter()
+            .filter(|(start, end)| start < end)
+            .collect()
+    }
+}
+
+/// 改行コードの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Eol {
+    /// Split lines on LF and CRLF, leaving other carriage returns as they are
+    #[default]
+    Keep,
+    /// Drop stray carriage returns and treat lone CRs as line breaks
+    Lf,
+}
+
+impl Eol {
+    /// テキストを行に分割する
+    fn split(self, text: &str) -> Vec<String> {
+        text.lines()
+            .flat_map(|line| self.split_line(line))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// LF・CRLFで区切った1行を、改行コードの扱いに従ってさらに分割する
+    fn split_line(self, line: &str) -> Vec<&str> {
+        match self {
+            Eol::Keep => vec![line],
+            // `\r\r\n`のように重なったCRは1つの改行として扱う
+            Eol::Lf => line.trim_end_matches('\r').split('\r').collect(),
+        }
+    }
+
+    /// 差分などの改行を含むテキストを正規化する
+    pub fn normalize(self, text: &str) -> String {
+        match self {
+            Eol::Keep => text.to_string(),
+            Eol::Lf => self
+                .split(text)
+                .into_iter()
+                .map(|line| line + "\n")
+                .collect(),
+        }
+    }
+}
+
+/// 出力する内容の正規化（改行コード・タブ・行末の空白・連続する空行）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Normalize {
+    /// 改行コードの扱い
+    pub eol: Eol,
+    /// タブを展開する幅（なしまたは0の場合はタブのまま）
+    pub tab_width: Option<usize>,
+    /// 行末の空白を取り除く
+    pub strip_trailing_ws: bool,
+    /// 連続する空行を1行にまとめる
+    pub squeeze_blank: bool,
+}
+
+impl Normalize {
+    /// 有効な正規化ごとに、そのオプション名と、それだけを無効にした設定を返す
+    pub fn each_enabled(&self) -> Vec<(String, Normalize)> {
+        let mut enabled = Vec::new();
+        if self.eol != Eol::Keep {
+            enabled.push((
+                "--eol lf".to_string(),
+                Normalize {
+                    eol: Eol::Keep,
+                    ..*self
+                },
+            ));
+        }
+        if let Some(width) = self.tab_width.filter(|&width| width > 0) {
+            let label = format!("--tab-width {}", width);
+            enabled.push((
+                label,
+                Normalize {
+                    tab_width: None,
+                    ..*self
+                },
+            ));
+        }
+        if self.strip_trailing_ws {
+            let without = Normalize {
+                strip_trailing_ws: false,
+                ..*self
+            };
+            enabled.push(("--strip-trailing-ws".to_string(), without));
+        }
+        if self.squeeze_blank {
+            let without = Normalize {
+                squeeze_blank: false,
+                ..*self
+            };
+            enabled.push(("--squeeze-blank".to_string(), without));
+        }
+        enabled
+    }
+
+    /// 1行の内容を正規化する（タブの展開と行末の空白の削除）
+    fn line(&self, line: &mut String) {
+        if let Some(width) = self
+            .tab_width
+            .filter(|&width| width > 0 && line.contains('\t'))
+        {
+            *line = expand_tabs(line, width);
+        }
+        if self.strip_trailing_ws {
+            line.truncate(line.trim_end().len());
+        }
+    }
+}
+
+/// タブを次のタブ位置までの空白に展開する
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = width - column % width;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+/// 行番号付きで表示する1行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberedLine<'a> {
+    /// 元の行番号（1始まり）と内容
+    Text(usize, &'a str),
+    /// 省略した行数
+    Omitted(usize),
+}
+
+impl NumberedLine<'_> {
+    /// 省略した行数を示すマーカー
+    pub fn marker(count: usize) -> String {
+        let unit = if count == 1 { "line" } else { "lines" };
+        format!("... [{} {} omitted] ...", count, unit)
+    }
+}
+
+/// 出力用に読み込んだファイルの内容
+#[derive(Debug, Clone)]
+pub struct FileContent {
+    /// 表示用のパス
+    pub path: String,
+    /// ファイルサイズ（バイト）
+    pub size: u64,
+    /// バイナリファイルかどうか
+    pub is_binary: bool,
+    /// UTF-8以外の文字コードから変換した場合はその名前
+    pub encoding: Option<&'static str>,
+    /// ファイル全体の行数
+    pub total_lines: usize,
+    /// 表示範囲を指定した場合の1始まりの開始行と終了行
+    pub range: Option<(usize, usize)>,
+    /// 出力対象の行（表示範囲を取り出し、max_linesで切り詰め済み）
+    pub lines: Vec<String>,
+    /// max_linesによって省略した行の範囲（1始まりの開始行と終了行）
+    pub omitted: Vec<(usize, usize)>,
+    /// max_file_sizeを超えたため内容を読み込まなかったかどうか
+    pub skipped: bool,
+    /// max_line_lengthによって短縮した行数
+    pub clipped_lines: usize,
+    /// 連続する空行をまとめたために表示しない行の番号（1始まり）
+    pub squeezed: Vec<usize>,
+}
+
+impl FileContent {
+    /// max_linesによって行が切り詰められたかどうか
+    pub fn is_truncated(&self) -> bool {
+        !self.omitted.is_empty()
+    }
+
+    /// 出力対象の行を元の行番号と省略箇所のマーカーとともに返す
+    pub fn numbered_lines(&self) -> Vec<NumberedLine<'_>> {
+        let mut number = self.range.map_or(1, |(start, _)| start);
+        let mut omitted = self.omitted.iter().peekable();
+        let mut squeezed = self.squeezed.iter().peekable();
+        let mut lines = self.lines.iter();
+        let mut numbered = Vec::new();
+
+        loop {
+            if let Some(&&(start, end)) = omitted.peek().filter(|(start, _)| *start == number) {
+                numbered.push(NumberedLine::Omitted(end + 1 - start));
+                number = end + 1;
+                omitted.next();
+                continue;
+            }
+
+            // まとめた空行はマーカーを出さずに行番号だけを進める
+            if squeezed.next_if(|&&n| n == number).is_some() {
+                number += 1;
+                continue;
+            }
+
+            let Some(line) = lines.next() else {
+                break;
+            };
+            numbered.push(NumberedLine::Text(number, line));
+            number += 1;
+        }
+
+        numbered
+    }
+
+    /// サイズの上限によって内容を省略した、または長い行を短縮した場合はその理由を返す
+    pub fn limited(&self, options: &ViewOptions) -> Option<limits::Limited> {
+        let reason = if self.skipped {
+            format!(
+                "skipped: {} exceeds --max-file-size {}",
+                limits::human_size(self.size),
+                limits::ByteSize(options.max_file_size.unwrap_or_default())
+            )
+        } else if self.clipped_lines > 0 {
+            let unit = if self.clipped_lines == 1 {
+                "line"
+            } else {
+                "lines"
+            };
+            format!(
+                "clipped {} {} to --max-line-length {}",
+                self.clipped_lines,
+                unit,
+                options.max_line_length.unwrap_or_default()
+            )
+        } else {
+            return None;
+        };
+
+        Some(limits::Limited {
+            path: self.path.clone(),
+            reason,
+        })
+    }
+
+    /// 出力対象の行を改行で連結したテキストを返す（省略箇所にはマーカーを入れる）
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for line in self.numbered_lines() {
+            match line {
+                NumberedLine::Text(_, line) => text.push_str(line),
+                NumberedLine::Omitted(count) => text.push_str(&NumberedLine::marker(count)),
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
 
 /// ファイルの内容を行番号付きで出力する
 pub fn file_view_with_lines<P: AsRef<Path>, W: Write>(
@@ -11,81 +387,769 @@ pub fn file_view_with_lines<P: AsRef<Path>, W: Write>(
     writer: &mut W,
     max_lines: usize,
 ) -> Result<()> {
-    let abs_path = path.as_ref().canonicalize().context(format!(
-        "Failed to resolve absolute path: {}",
-        path.as_ref().display()
-    ))?;
+    file_view(
+        path,
+        writer,
+        &ViewOptions::new(max_lines, OutputFormat::Plain),
+    )?;
+    Ok(())
+}
 
-    if abs_path.is_dir() {
-        anyhow::bail!("Cannot render directory as file: {}", abs_path.display());
+/// ファイルの内容を指定されたフォーマットで出力し、読み込んだ内容を返す
+pub fn file_view<P: AsRef<Path>, W: Write>(
+    path: P,
+    writer: &mut W,
+    options: &ViewOptions,
+) -> Result<FileContent> {
+    let abs_path = resolve_path(path.as_ref(), options)?;
+
+    match options.format {
+        OutputFormat::Plain => print_file_plain(&path, &abs_path, writer, options),
+        OutputFormat::Markdown => print_file_markdown(&path, &abs_path, writer, options),
+        OutputFormat::Xml => print_file_xml(&path, &abs_path, writer, options),
+        OutputFormat::Json | OutputFormat::Jsonl => anyhow::bail!(
+            "JSON output is rendered for the whole document, not per file: {}",
+            abs_path.display()
+        ),
     }
+}
 
-    if is_binary_file(&abs_path)? {
-        print_file_header(&path, writer)?;
+/// 変更差分（統一diff形式）を指定されたフォーマットで出力する
+pub fn diff_view<P: AsRef<Path>, W: Write>(
+    path: P,
+    status: &str,
+    diff: &str,
+    writer: &mut W,
+    options: &ViewOptions,
+) -> Result<()> {
+    let display = display_path(path.as_ref(), options.git_root.as_deref());
+
+    match options.format {
+        OutputFormat::Plain => {
+            print_file_header(path, &format!(" [{}]", status), writer, options)?;
+            write!(writer, "{}", diff)?;
+            print_file_footer(writer)?;
+        }
+        OutputFormat::Markdown => {
+            writeln!(writer, "### {} [{}]\n", display, status)?;
+            format::write_fenced_block(writer, "diff", diff)?;
+            writeln!(writer)?;
+        }
+        OutputFormat::Xml => {
+            writeln!(
+                writer,
+                "<diff path=\"{}\" status=\"{}\">",
+                format::escape_xml(&display),
+                status
+            )?;
+            format::write_cdata(writer, diff)?;
+            writeln!(writer, "</diff>")?;
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => anyhow::bail!(
+            "JSON output is rendered for the whole document, not per file: {}",
+            display
+        ),
+    }
+
+    Ok(())
+}
+
+/// ファイルをヘッダー・行番号付き本文・フッターの形式で出力する
+fn print_file_plain<P: AsRef<Path>, W: Write>(
+    path: P,
+    abs_path: &Path,
+    writer: &mut W,
+    options: &ViewOptions,
+) -> Result<FileContent> {
+    let content = read_file_content(&path, abs_path, options)?;
+
+    print_file_header(&path, &range_suffix(content.range), writer, options)?;
+    if content.skipped {
+        writeln!(writer, "{}", skipped_marker(content.size))?;
+    } else if content.is_binary {
         writeln!(writer, "[binary file omitted]")?;
-        print_file_footer(writer)?;
+    } else {
+        print_file_body_with_lines(&content, writer)?;
+    }
+    print_file_footer(writer)?;
+
+    Ok(content)
+}
+
+/// ファイルをMarkdownの見出しとコードブロックの形式で出力する
+fn print_file_markdown<P: AsRef<Path>, W: Write>(
+    path: P,
+    abs_path: &Path,
+    writer: &mut W,
+    options: &ViewOptions,
+) -> Result<FileContent> {
+    let content = read_file_content(&path, abs_path, options)?;
+
+    match content.range {
+        Some((start, end)) => writeln!(writer, "### {}:{}-{}\n", content.path, start, end)?,
+        None => writeln!(writer, "### {}\n", content.path)?,
+    }
+
+    if content.skipped {
+        writeln!(writer, "{}\n", skipped_marker(content.size))?;
+        return Ok(content);
+    }
+    if content.is_binary {
+        writeln!(writer, "[binary file omitted]\n")?;
+        return Ok(content);
+    }
+
+    format::write_fenced_block(writer, format::language_for_path(abs_path), &content.text())?;
+    writeln!(writer)?;
+
+    Ok(content)
+}
+
+/// ファイルをXMLの`<file>`要素として出力する
+fn print_file_xml<P: AsRef<Path>, W: Write>(
+    path: P,
+    abs_path: &Path,
+    writer: &mut W,
+    options: &ViewOptions,
+) -> Result<FileContent> {
+    let content = read_file_content(&path, abs_path, options)?;
+    let escaped_path = format::escape_xml(&content.path);
+
+    if content.skipped {
+        writeln!(
+            writer,
+            "<file path=\"{}\" size=\"{}\" skipped=\"true\" />",
+            escaped_path, content.size
+        )?;
+        return Ok(content);
+    }
+    if content.is_binary {
+        writeln!(writer, "<file path=\"{}\" binary=\"true\" />", escaped_path)?;
+        return Ok(content);
+    }
+
+    let range = match content.range {
+        Some((start, end)) => format!(" range=\"{}-{}\"", start, end),
+        None => String::new(),
+    };
+    let omitted = match content
+        .omitted
+        .iter()
+        .map(|(start, end)| end + 1 - start)
+        .sum()
+    {
+        0 => String::new(),
+        count => format!(" omitted=\"{}\"", count),
+    };
+
+    let encoding = match content.encoding {
+        Some(encoding) => format!(" encoding=\"{}\"", encoding),
+        None => String::new(),
+    };
+
+    writeln!(
+        writer,
+        "<file path=\"{}\"{} lines=\"{}\"{} truncated=\"{}\"{}>",
+        escaped_path,
+        encoding,
+        content.total_lines,
+        range,
+        content.is_truncated(),
+        omitted
+    )?;
+    format::write_cdata(writer, &content.text())?;
+    writeln!(writer, "</file>")?;
+
+    Ok(content)
+}
+
+/// ファイルを読み込み、出力用の内容を取得する
+pub fn load_file<P: AsRef<Path>>(path: P, options: &ViewOptions) -> Result<FileContent> {
+    let abs_path = resolve_path(path.as_ref(), options)?;
+    read_file_content(&path, &abs_path, options)
+}
+
+/// 解決済みの絶対パスからファイルを読み込む
+fn read_file_content<P: AsRef<Path>>(
+    path: P,
+    abs_path: &Path,
+    options: &ViewOptions,
+) -> Result<FileContent> {
+    let mut content = FileContent {
+        path: display_path(path.as_ref(), options.git_root.as_deref()),
+        size: 0,
+        is_binary: false,
+        encoding: None,
+        total_lines: 0,
+        range: None,
+        lines: Vec::new(),
+        omitted: Vec::new(),
+        skipped: false,
+        clipped_lines: 0,
+        squeezed: Vec::new(),
+    };
+
+    // 上限を超えるファイルは内容を読み込まずにサイズだけを記録する
+    if let Some(size) = oversized(abs_path, options)? {
+        content.size = size;
+        content.skipped = true;
+        return Ok(content);
+    }
+
+    // 先頭から読めば済むファイルは、全体を読み込まずに1行ずつ読み込む
+    let streamed =
+        is_streamable(abs_path, options) && stream_lines(abs_path, options, &mut content)?;
+    if !streamed {
+        read_lines(abs_path, options, &mut content)?;
+    }
+    if content.is_binary {
+        return Ok(content);
+    }
+
+    for line in &mut content.lines {
+        options.normalize.line(line);
+    }
+    if options.normalize.squeeze_blank {
+        squeeze_blank_lines(&mut content);
+    }
+
+    // 圧縮されたバンドルなどの長い行は先頭だけを残す
+    if let Some(max_line_length) = options.max_line_length {
+        for line in &mut content.lines {
+            content.clipped_lines += usize::from(clip_line(line, max_line_length));
+        }
+    }
+
+    Ok(content)
+}
+
+/// ファイル全体を読み込まずに先頭から1行ずつ読み込めるかどうか
+/// （スナップショット・ロックファイルの要約・表示範囲・末尾や中央を残す切り詰めではファイル全体が必要になる）
+fn is_streamable(abs_path: &Path, options: &ViewOptions) -> bool {
+    options.snapshot.is_none()
+        && options.region.is_none()
+        && (options.max_lines == 0 || options.truncate == Truncate::Head)
+        && (options.lockfiles != LockfilePolicy::Summary || Lockfile::detect(abs_path).is_none())
+}
+
+/// ファイルを先頭から1行ずつ読み込み、表示する行だけを保持する（残りの行は数えるだけにする）
+/// UTF-8として読み込めないため、文字コードの判定にファイル全体が必要な場合はfalseを返す
+fn stream_lines(abs_path: &Path, options: &ViewOptions, content: &mut FileContent) -> Result<bool> {
+    let mut file =
+        File::open(abs_path).context(format!("Failed to open file: {}", abs_path.display()))?;
+    let read_error = || format!("Failed to read file: {}", abs_path.display());
+
+    // 先頭のバイト列で文字コードとバイナリかどうかを判定する
+    let mut head = Vec::new();
+    (&mut file)
+        .take(BINARY_CHECK_BYTES as u64)
+        .read_to_end(&mut head)
+        .with_context(read_error)?;
+    if !options.encoding.is_utf8_prefix(&head) {
+        return Ok(false);
+    }
+    content.size = file.metadata().with_context(read_error)?.len();
+    if is_binary(&head) {
+        content.is_binary = true;
+        return Ok(true);
+    }
+
+    let max_lines = options.max_lines;
+    let mut reader = BufReader::new(head.as_slice().chain(file));
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if reader
+            .read_until(b'\n', &mut buf)
+            .with_context(read_error)?
+            == 0
+        {
+            break;
+        }
+
+        // `str::lines`と同じく、行末のLFとその直前のCRを取り除く
+        if buf.last() == Some(&b'\n') {
+            buf.pop();
+            if buf.last() == Some(&b'\r') {
+                buf.pop();
+            }
+        }
+        let line = match std::str::from_utf8(&buf) {
+            Ok(line) => line,
+            Err(_) if options.encoding == Charset::Auto => return Ok(false),
+            Err(_) => &String::from_utf8_lossy(&buf),
+        };
+
+        for line in options.normalize.eol.split_line(line) {
+            if max_lines == 0 || content.total_lines < max_lines {
+                content.lines.push(line.to_string());
+            }
+            content.total_lines += 1;
+        }
+    }
+
+    content.omitted = options
+        .truncate
+        .omitted(content.total_lines, max_lines)
+        .into_iter()
+        .map(|(start, end)| (start + 1, end))
+        .collect();
+
+    Ok(true)
+}
+
+/// ファイル全体を読み込んで文字コードを変換し、表示範囲と切り詰め方に従って表示する行を取り出す
+fn read_lines(abs_path: &Path, options: &ViewOptions, content: &mut FileContent) -> Result<()> {
+    let bytes = read_bytes(abs_path, options)?;
+    let max_lines = options.max_lines;
+    content.size = bytes.len() as u64;
+
+    // UTF-16はASCII文字にNULを含むため、バイナリとして扱わない
+    let encoding = options.encoding.resolve(&bytes);
+    content.is_binary = !charset::is_utf16(encoding) && is_binary(&bytes);
+
+    if content.is_binary {
         return Ok(());
     }
 
-    let file =
-        File::open(&abs_path).context(format!("Failed to open file: {}", abs_path.display()))?;
+    // 変換できないバイトは置換文字にして、読み込みを中断しない
+    let text = charset::decode(&bytes, encoding);
+    content.encoding = (encoding != encoding_rs::UTF_8).then(|| encoding.name());
+    let lines = options.normalize.eol.split(&text);
+    content.total_lines = lines.len();
 
-    print_file_header(&path, writer)?;
-    print_file_body_with_lines(file, writer, max_lines)?;
-    print_file_footer(writer)?;
+    // 表示範囲を指定した場合は、その範囲の行を対象にする
+    let shown = match &options.region {
+        Some(region) => {
+            let (start, end) = region.locate(&lines).context(format!(
+                "Failed to locate {} in {}",
+                region,
+                abs_path.display()
+            ))?;
+            content.range = Some((start, end));
+            start - 1..end
+        }
+        None => 0..lines.len(),
+    };
+
+    // 切り詰め方に従って省略する行を除き、省略した範囲を元の行番号で記録する
+    let omitted = options.truncate.omitted(shown.len(), max_lines);
+    content.omitted = omitted
+        .iter()
+        .map(|(start, end)| (shown.start + start + 1, shown.start + end))
+        .collect();
+    content.lines = lines
+        .into_iter()
+        .enumerate()
+        .skip(shown.start)
+        .take(shown.len())
+        .filter(|(i, _)| {
+            let offset = i - shown.start;
+            !omitted
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&offset))
+        })
+        .map(|(_, line)| line)
+        .collect();
 
     Ok(())
 }
 
-/// ファイルヘッダーを出力する
-fn print_file_header<P: AsRef<Path>, W: Write>(path: P, writer: &mut W) -> Result<()> {
-    let path = path.as_ref();
+/// 元のファイルで連続する空行を、最初の1行だけ残して取り除く
+fn squeeze_blank_lines(content: &mut FileContent) {
+    let numbers = content
+        .numbered_lines()
+        .into_iter()
+        .filter_map(|line| match line {
+            NumberedLine::Text(number, _) => Some(number),
+            NumberedLine::Omitted(_) => None,
+        })
+        .collect::<Vec<_>>();
 
-    // パスをGitルートからの相対パスに変換する
-    if let Ok(git_root) = gitutil::get_git_root(path) {
-        if let Ok(rel_to_git_root) = path.strip_prefix(&git_root) {
-            writeln!(
-                writer,
-                "\n\n/{}",
-                rel_to_git_root.to_string_lossy().replace('\\', "/")
-            )?;
+    let mut previous = None;
+    let mut kept = Vec::new();
+    for (number, line) in numbers.into_iter().zip(std::mem::take(&mut content.lines)) {
+        let blank = line.trim().is_empty();
+        if blank && previous == Some((number - 1, true)) {
+            content.squeezed.push(number);
         } else {
-            writeln!(writer, "\n\n/{}", path.to_string_lossy().replace('\\', "/"))?;
+            kept.push(line);
         }
-    } else {
-        // カレントディレクトリからの相対パスに変換
-        let rel_path = if let Ok(cwd) = std::env::current_dir() {
-            if let Ok(rel) = path.strip_prefix(&cwd) {
-                rel.to_path_buf()
+        previous = Some((number, blank));
+    }
+    content.lines = kept;
+}
+
+/// ファイルがmax_file_sizeを超える場合はそのサイズを返す
+fn oversized(abs_path: &Path, options: &ViewOptions) -> Result<Option<u64>> {
+    let Some(max_file_size) = options.max_file_size else {
+        return Ok(None);
+    };
+
+    let size = match &options.snapshot {
+        Some(snapshot) => snapshot.read(abs_path)?.len() as u64,
+        None => std::fs::metadata(abs_path)
+            .context(format!("Failed to read file: {}", abs_path.display()))?
+            .len(),
+    };
+
+    Ok((size > max_file_size).then_some(size))
+}
+
+/// 行がmax_chars文字を超える場合は超えた部分をマーカーに置き換え、trueを返す
+fn clip_line(line: &mut String, max_chars: usize) -> bool {
+    let Some((end, _)) = line.char_indices().nth(max_chars) else {
+        return false;
+    };
+
+    let clipped = line[end..].chars().count();
+    line.truncate(end);
+    line.push_str(&format!(" ... [{} chars clipped]", clipped));
+    true
+}
+
+/// max_file_sizeを超えて内容を省略したファイルのマーカー
+fn skipped_marker(size: u64) -> String {
+    format!(
+        "[file skipped: {} exceeds --max-file-size]",
+        limits::human_size(size)
+    )
+}
+
+/// 読み込み対象の絶対パスを解決する（スナップショットの場合は作業ツリー上に存在しなくてもよい）
+fn resolve_path(path: &Path, options: &ViewOptions) -> Result<PathBuf> {
+    if options.snapshot.is_some() {
+        return Ok(path.to_path_buf());
+    }
+
+    let abs_path = path.canonicalize().context(format!(
+        "Failed to resolve absolute path: {}",
+        path.display()
+    ))?;
+
+    if abs_path.is_dir() {
+        anyhow::bail!("Cannot render directory as file: {}", abs_path.display());
+    }
+
+    Ok(abs_path)
+}
+
+/// ファイルの内容を作業ツリーまたはスナップショットから読み込む（要約するロックファイルは要約を返す）
+fn read_bytes(abs_path: &Path, options: &ViewOptions) -> Result<Vec<u8>> {
+    let bytes = match &options.snapshot {
+        Some(snapshot) => snapshot.read(abs_path)?,
+        None => std::fs::read(abs_path)
+            .context(format!("Failed to read file: {}", abs_path.display()))?,
+    };
+
+    if options.lockfiles != LockfilePolicy::Summary {
+        return Ok(bytes);
+    }
+
+    // 解析できない場合は元の内容をそのまま表示する
+    let summary = Lockfile::detect(abs_path)
+        .and_then(|kind| summarize_lockfile(kind, &String::from_utf8_lossy(&bytes)));
+
+    Ok(match summary {
+        Some(packages) => {
+            let noun = if packages.len() == 1 {
+                "package"
             } else {
-                path.to_path_buf()
+                "packages"
+            };
+            let mut text = format!("# {} {} (lockfile summary)\n", packages.len(), noun);
+            for package in packages {
+                text.push_str(&package);
+                text.push('\n');
             }
-        } else {
-            path.to_path_buf()
+            text.into_bytes()
+        }
+        None => bytes,
+    })
+}
+
+/// ロックファイルを解析し、「package@version」の一覧を名前順に返す
+fn summarize_lockfile(kind: Lockfile, text: &str) -> Option<Vec<String>> {
+    let mut packages = match kind {
+        Lockfile::TomlPackages => toml_packages(text)?,
+        Lockfile::Npm => npm_packages(text)?,
+        Lockfile::Yarn => yarn_packages(text),
+        Lockfile::Pnpm => pnpm_packages(text),
+        Lockfile::Pipfile => pipfile_packages(text)?,
+        Lockfile::Composer => composer_packages(text)?,
+        Lockfile::Gemfile => gemfile_packages(text),
+        Lockfile::GoSum => go_sum_packages(text),
+        Lockfile::Other => return None,
+    };
+
+    packages.sort();
+    packages.dedup();
+    Some(
+        packages
+            .into_iter()
+            .map(|(name, version)| format!("{}@{}", name, version))
+            .collect(),
+    )
+}
+
+/// 名前とバージョンの組
+type Package = (String, String);
+
+/// `[[package]]`の配列を持つTOML（Cargo.lock・poetry.lock・uv.lock・pdm.lock）を解析する
+fn toml_packages(text: &str) -> Option<Vec<Package>> {
+    let value = text.parse::<toml::Table>().ok()?;
+    let packages = value.get("package")?.as_array()?;
+
+    Some(
+        packages
+            .iter()
+            .filter_map(|package| {
+                let name = package.get("name")?.as_str()?;
+                let version = package.get("version")?.as_str()?;
+                Some((name.to_string(), version.to_string()))
+            })
+            .collect(),
+    )
+}
+
+/// package-lock.json（v2以降の`packages`、v1の`dependencies`）を解析する
+fn npm_packages(text: &str) -> Option<Vec<Package>> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    let mut packages = Vec::new();
+
+    if let Some(entries) = value.get("packages").and_then(|p| p.as_object()) {
+        for (key, entry) in entries {
+            // 空のキーはプロジェクト自身
+            let Some(version) = entry.get("version").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let name = match key.rfind("node_modules/") {
+                Some(i) => &key[i + "node_modules/".len()..],
+                None => continue,
+            };
+            packages.push((name.to_string(), version.to_string()));
+        }
+        return Some(packages);
+    }
+
+    fn collect(dependencies: &serde_json::Value, packages: &mut Vec<Package>) {
+        let Some(dependencies) = dependencies.as_object() else {
+            return;
         };
+        for (name, entry) in dependencies {
+            if let Some(version) = entry.get("version").and_then(|v| v.as_str()) {
+                packages.push((name.clone(), version.to_string()));
+            }
+            if let Some(nested) = entry.get("dependencies") {
+                collect(nested, packages);
+            }
+        }
+    }
 
-        writeln!(writer, "/{}", rel_path.to_string_lossy().replace('\\', "/"))?;
+    collect(value.get("dependencies")?, &mut packages);
+    Some(packages)
+}
+
+/// yarn.lock（v1の`version "x"`、Berryの`version: x`）を解析する
+fn yarn_packages(text: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+    let mut name = None;
+
+    for line in text.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(' ') {
+            // 「"pkg@^1.0.0", pkg@^1.1.0:」の最初の指定から名前を取り出す（スコープの`@`は除く）
+            let spec = line
+                .trim_end_matches(':')
+                .split(',')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_matches('"');
+            name = split_version(spec)
+                .map(|(name, _)| name.to_string())
+                .filter(|name| name != "__metadata");
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let version = trimmed
+            .strip_prefix("version ")
+            .or_else(|| trimmed.strip_prefix("version: "));
+        if let (Some(version), Some(name)) = (version, name.take()) {
+            packages.push((name, version.trim_matches('"').to_string()));
+        }
     }
 
-    writeln!(writer, "{}", "-".repeat(80))?;
-    Ok(())
+    packages
+}
+
+/// pnpm-lock.yaml の`packages:`セクションのキー（`/pkg@1.0.0`、`/pkg/1.0.0`、`pkg@1.0.0(peer)`）を解析する
+fn pnpm_packages(text: &str) -> Vec<Package> {
+    let mut packages = Vec::new();
+    let mut in_packages = false;
+
+    for line in text.lines() {
+        if !line.starts_with(' ') && !line.trim().is_empty() {
+            in_packages = line.trim_end() == "packages:";
+            continue;
+        }
+
+        let Some(key) = line.strip_prefix("  ") else {
+            continue;
+        };
+        if !in_packages || key.starts_with(' ') || !key.trim_end().ends_with(':') {
+            continue;
+        }
+
+        let key = key
+            .trim_end()
+            .trim_end_matches(':')
+            .trim_matches(|c| c == '\'' || c == '"');
+        let key = key.trim_start_matches('/');
+        let key = key.split('(').next().unwrap_or(key);
+
+        let package = split_version(key).or_else(|| key.rsplit_once('/'));
+        if let Some((name, version)) = package {
+            packages.push((name.to_string(), version.to_string()));
+        }
+    }
+
+    packages
+}
+
+/// 「pkg@1.0.0」を名前とバージョンに分ける（スコープ付きの名前の先頭の`@`では分けない）
+fn split_version(spec: &str) -> Option<(&str, &str)> {
+    let scope = usize::from(spec.starts_with('@'));
+    let at = spec[scope..].find('@')? + scope;
+    Some((&spec[..at], &spec[at + 1..]))
+}
+
+/// Pipfile.lock の`default`・`develop`を解析する
+fn pipfile_packages(text: &str) -> Option<Vec<Package>> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    let mut packages = Vec::new();
+
+    for section in ["default", "develop"] {
+        let Some(entries) = value.get(section).and_then(|s| s.as_object()) else {
+            continue;
+        };
+        for (name, entry) in entries {
+            let version = entry
+                .get("version")
+                .and_then(|v| v.as_str())
+                .unwrap_or("*")
+                .trim_start_matches("==");
+            packages.push((name.clone(), version.to_string()));
+        }
+    }
+
+    Some(packages)
+}
+
+/// composer.lock の`packages`・`packages-dev`を解析する
+fn composer_packages(text: &str) -> Option<Vec<Package>> {
+    let value = serde_json::from_str::<serde_json::Value>(text).ok()?;
+    let mut packages = Vec::new();
+
+    for section in ["packages", "packages-dev"] {
+        let Some(entries) = value.get(section).and_then(|s| s.as_array()) else {
+            continue;
+        };
+        for entry in entries {
+            let name = entry.get("name").and_then(|v| v.as_str());
+            let version = entry.get("version").and_then(|v| v.as_str());
+            if let (Some(name), Some(version)) = (name, version) {
+                packages.push((name.to_string(), version.to_string()));
+            }
+        }
+    }
+
+    Some(packages)
+}
+
+/// Gemfile.lock の`specs:`に並ぶ「    name (version)」を解析する
+fn gemfile_packages(text: &str) -> Vec<Package> {
+    text.lines()
+        .filter(|line| line.starts_with("    ") && !line.starts_with("     "))
+        .filter_map(|line| {
+            let (name, version) = line.trim().split_once(" (")?;
+            Some((name.to_string(), version.trim_end_matches(')').to_string()))
+        })
+        .collect()
 }
 
-/// ファイル内容を行番号付きで出力する
-fn print_file_body_with_lines<R: Read, W: Write>(
-    reader: R,
+/// go.sum の「module version[/go.mod] hash」を解析する
+fn go_sum_packages(text: &str) -> Vec<Package> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let module = fields.next()?;
+            let version = fields.next()?.trim_end_matches("/go.mod");
+            Some((module.to_string(), version.to_string()))
+        })
+        .collect()
+}
+
+/// ファイルのヘッダーを出力する（`suffix`は行範囲や差分の状態など、パスの後に付ける表示）
+fn print_file_header<P: AsRef<Path>, W: Write>(
+    path: P,
+    suffix: &str,
     writer: &mut W,
-    max_lines: usize,
+    options: &ViewOptions,
 ) -> Result<()> {
-    let buf_reader = BufReader::new(reader);
+    writeln!(
+        writer,
+        "/{}{}",
+        display_path(path.as_ref(), options.git_root.as_deref()),
+        suffix
+    )?;
+    writeln!(writer, "{}", "-".repeat(80))?;
+    Ok(())
+}
 
-    for (line_num, line) in buf_reader.lines().enumerate() {
-        if max_lines > 0 && line_num >= max_lines {
-            break;
-        }
+/// ヘッダーに付ける行範囲の表示を返す
+fn range_suffix(range: Option<(usize, usize)>) -> String {
+    match range {
+        Some((start, end)) => format!(":{}-{}", start, end),
+        None => String::new(),
+    }
+}
+
+/// 表示用のパスを取得する（Gitルートからの相対パス、なければカレントディレクトリからの相対パス）
+pub fn display_path(path: &Path, git_root: Option<&Path>) -> String {
+    git_relative_path(path, git_root).unwrap_or_else(|| cwd_relative_path(path))
+}
 
-        let line = line.context("Error reading file")?;
-        writeln!(writer, "{:4} | {}", line_num + 1, line)?;
+/// パスを解決済みのGitルートからの相対パスに変換する（Gitルートの外にある場合はNone）
+fn git_relative_path(path: &Path, git_root: Option<&Path>) -> Option<String> {
+    let rel_path = path.strip_prefix(git_root?).ok()?;
+    Some(rel_path.to_string_lossy().replace('\\', "/"))
+}
+
+/// パスをカレントディレクトリからの相対パスに変換する
+/// （ホストの絶対パスを出力しないよう、カレントディレクトリの外にあるファイルはファイル名のみにする）
+fn cwd_relative_path(path: &Path) -> String {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let rel_path = match path.strip_prefix(&cwd) {
+        Ok(rel) => rel,
+        Err(_) if path.is_absolute() => path.file_name().map_or(path, Path::new),
+        Err(_) => path,
+    };
+
+    rel_path.to_string_lossy().replace('\\', "/")
+}
+
+/// ファイル内容を行番号付きで出力する（省略箇所にはマーカーを出力する）
+fn print_file_body_with_lines<W: Write>(content: &FileContent, writer: &mut W) -> Result<()> {
+    for line in content.numbered_lines() {
+        match line {
+            NumberedLine::Text(number, line) => writeln!(writer, "{:4} | {}", number, line)?,
+            NumberedLine::Omitted(count) => writeln!(writer, "{}", NumberedLine::marker(count))?,
+        }
     }
 
     Ok(())
@@ -97,17 +1161,7 @@ fn print_file_footer<W: Write>(writer: &mut W) -> Result<()> {
     Ok(())
 }
 
-/// バイナリファイルかどうかを判定する（最初の8000バイトにnull文字が含まれるかどうかで判定）
-fn is_binary_file<P: AsRef<Path>>(path: P) -> Result<bool> {
-    let mut file = File::open(path.as_ref())
-        .context(format!("Failed to open file: {}", path.as_ref().display()))?;
-
-    const MAX_BYTES: usize = 8000;
-    let mut buf = vec![0u8; MAX_BYTES];
-
-    let n = file
-        .read(&mut buf)
-        .context(format!("Failed to read file: {}", path.as_ref().display()))?;
-
-    Ok(buf[..n].contains(&0))
+/// バイナリファイルかどうかを判定する（先頭8000バイトにnull文字が含まれるかどうかで判定）
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0)
 }
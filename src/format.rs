@@ -0,0 +1,159 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// 出力フォーマット
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Plain text with line numbers
+    #[default]
+    Plain,
+    /// Markdown headings with fenced, language-tagged code blocks
+    Markdown,
+    /// XML elements for LLM prompts
+    Xml,
+    /// A single JSON document with the tree, files and summary
+    Json,
+    /// One JSON record per line, for streaming
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// ドキュメント全体を構造化データとして出力するフォーマットかどうか
+    pub fn is_structured(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Jsonl)
+    }
+}
+
+/// 拡張子からコードブロックの言語タグを推測する
+pub fn language_for_path<P: AsRef<Path>>(path: P) -> &'static str {
+    let path = path.as_ref();
+
+    // 拡張子を持たない代表的なファイル名
+    let file_name = path
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match file_name.as_str() {
+        "Dockerfile" => return "dockerfile",
+        "Makefile" | "GNUmakefile" => return "makefile",
+        "CMakeLists.txt" => return "cmake",
+        _ => {}
+    }
+
+    let ext = path
+        .extension()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "jsx",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "tsx",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "scala" => "scala",
+        "swift" => "swift",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "pl" | "pm" => "perl",
+        "lua" => "lua",
+        "dart" => "dart",
+        "ex" | "exs" => "elixir",
+        "erl" => "erlang",
+        "hs" => "haskell",
+        "ml" | "mli" => "ocaml",
+        "clj" | "cljs" => "clojure",
+        "r" => "r",
+        "sh" | "bash" | "zsh" => "bash",
+        "fish" => "fish",
+        "ps1" => "powershell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "scss" => "scss",
+        "sass" => "sass",
+        "less" => "less",
+        "vue" => "vue",
+        "svelte" => "svelte",
+        "json" => "json",
+        "yml" | "yaml" => "yaml",
+        "toml" => "toml",
+        "xml" => "xml",
+        "ini" | "cfg" => "ini",
+        "md" | "markdown" => "markdown",
+        "proto" => "protobuf",
+        "graphql" | "gql" => "graphql",
+        "tf" => "hcl",
+        "nix" => "nix",
+        "diff" | "patch" => "diff",
+        _ => "",
+    }
+}
+
+/// 内容に含まれるバッククォートの連続より長いコードフェンスを返す
+pub fn code_fence(content: &str) -> String {
+    let mut longest = 0;
+    let mut current = 0;
+
+    for c in content.chars() {
+        if c == '`' {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+
+    "`".repeat((longest + 1).max(3))
+}
+
+/// 内容をフェンス付きコードブロックとして出力する
+pub fn write_fenced_block<W: Write>(writer: &mut W, language: &str, content: &str) -> Result<()> {
+    let fence = code_fence(content);
+
+    writeln!(writer, "{}{}", fence, language)?;
+    write!(writer, "{}", content)?;
+    if !content.is_empty() && !content.ends_with('\n') {
+        writeln!(writer)?;
+    }
+    writeln!(writer, "{}", fence)?;
+
+    Ok(())
+}
+
+/// XMLのテキスト・属性値として使えるように特殊文字をエスケープする
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 内容をCDATAセクションとして出力する（内容中の`]]>`はセクションを分割して表現する）
+pub fn write_cdata<W: Write>(writer: &mut W, content: &str) -> Result<()> {
+    writeln!(
+        writer,
+        "<![CDATA[{}]]>",
+        content.replace("]]>", "]]]]><![CDATA[>")
+    )?;
+    Ok(())
+}
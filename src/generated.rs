@@ -0,0 +1,334 @@
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::filefilter;
+
+/// 先頭部分として読み込む最大バイト数
+pub const HEADER_BYTES: usize = 8192;
+
+/// ヘッダーとして確認する先頭の行数
+const HEADER_CHECK_LINES: usize = 20;
+
+/// 圧縮されたファイルとみなす1行の長さ
+const MINIFIED_LINE_LENGTH: usize = 500;
+
+/// 組み込みのルール
+struct Builtin {
+    /// ルール名（`--explain-excluded`の表示や無効化に使う）
+    name: &'static str,
+    /// パスのglobパターン（空の場合はすべてのファイル）
+    paths: &'static [&'static str],
+    /// 先頭部分にマッチさせる正規表現
+    header: Option<&'static str>,
+    /// 長い行を含む（圧縮されている）ことを条件にする
+    minified: bool,
+}
+
+/// 組み込みのルール（特定のツールのものから順に確認し、最初にマッチしたルールを報告する）
+const BUILTIN_RULES: &[Builtin] = &[
+    Builtin {
+        name: "protoc",
+        paths: &[
+            "*.pb.go",
+            "*.pb.cc",
+            "*.pb.h",
+            "*_pb2.py",
+            "*_pb2.pyi",
+            "*_pb2_grpc.py",
+            "*_pb.js",
+            "*_pb.d.ts",
+            "*_grpc_pb.js",
+            "*.pb.swift",
+            "*.pb.dart",
+            "*.pbenum.dart",
+            "*.pbjson.dart",
+            "*.pbgrpc.dart",
+        ],
+        header: None,
+        minified: false,
+    },
+    Builtin {
+        name: "protoc",
+        paths: &[],
+        header: Some(
+            r"(?i)generated by the protocol buffer compiler|(code )?generated by protoc-gen-[\w-]+",
+        ),
+        minified: false,
+    },
+    Builtin {
+        name: "bindgen",
+        paths: &[],
+        header: Some(r"(?i)automatically generated by rust-bindgen"),
+        minified: false,
+    },
+    Builtin {
+        name: "sqlc",
+        paths: &[],
+        header: Some(r"(?i)code generated by sqlc\b"),
+        minified: false,
+    },
+    Builtin {
+        name: "openapi",
+        paths: &[".openapi-generator/"],
+        header: None,
+        minified: false,
+    },
+    Builtin {
+        name: "openapi",
+        paths: &[],
+        header: Some(
+            r"(?i)generated by:? (the )?openapi[- ]generator|openapi-generator\.tech|(auto generated|generated) by (openapi|swagger)[- ]codegen",
+        ),
+        minified: false,
+    },
+    Builtin {
+        name: "go-generated",
+        paths: &[],
+        header: Some(r"(?m)^// Code generated .* DO NOT EDIT\.$"),
+        minified: false,
+    },
+    Builtin {
+        name: "lockfile",
+        paths: filefilter::LOCKFILE_NAMES,
+        header: None,
+        minified: false,
+    },
+    Builtin {
+        name: "source-map",
+        paths: &["*.js.map", "*.mjs.map", "*.cjs.map", "*.css.map"],
+        header: None,
+        minified: false,
+    },
+    Builtin {
+        name: "minified",
+        paths: &["*.min.js", "*.min.mjs", "*.min.css"],
+        header: None,
+        minified: false,
+    },
+    Builtin {
+        name: "minified",
+        paths: &["*.js", "*.mjs", "*.cjs", "*.css"],
+        header: None,
+        minified: true,
+    },
+    Builtin {
+        name: "generated-marker",
+        paths: &[],
+        // コメントの書き出しが自動生成を示す場合か、「生成」と「編集禁止」が同じ行にある場合のみ
+        // （「generated by the user」のような説明文にはマッチさせない）
+        header: Some(concat!(
+            r"(?mi)@generated\b",
+            r"|^\W*(this (file|code) (is|was|has been) )?(auto-?generated|automatically generated|machine generated)\b",
+            r"|^\W*(this (file|code) (is|was) )?generated\b.*\bdo not (edit|modify)\b",
+            r"|^\W*do not (edit|modify)\b.*\bgenerated\b",
+        )),
+        minified: false,
+    },
+];
+
+/// 設定ファイルで追加する自動生成ファイルのルール
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// ルール名
+    pub name: String,
+    /// パスのglobパターン（`/`を含まない場合はファイル名、末尾が`/`の場合はディレクトリにマッチ）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// 先頭部分にマッチさせる正規表現（pathsと両方指定した場合は両方を満たすファイル）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+}
+
+/// 自動生成ファイルを判定するルール
+#[derive(Debug)]
+struct Rule {
+    name: String,
+    /// ファイル名にマッチさせるglob
+    name_globs: GlobSet,
+    /// ルートからの相対パスにマッチさせるglob
+    path_globs: GlobSet,
+    /// パスの条件があるかどうか
+    has_paths: bool,
+    /// 先頭部分にマッチさせる正規表現
+    header: Option<Regex>,
+    /// 長い行を含むことを条件にする
+    minified: bool,
+}
+
+impl Rule {
+    /// パス・正規表現からルールを作成する
+    fn new(name: &str, paths: &[&str], header: Option<&str>, minified: bool) -> Result<Self> {
+        let mut name_globs = GlobSetBuilder::new();
+        let mut path_globs = GlobSetBuilder::new();
+
+        for pattern in paths {
+            let (pattern, is_dir) = match pattern.strip_suffix('/') {
+                Some(dir) => (dir, true),
+                None => (*pattern, false),
+            };
+            let is_anchored = pattern.contains('/');
+            let pattern = pattern.trim_start_matches('/');
+
+            let (builder, pattern) = match (is_anchored, is_dir) {
+                (false, false) => (&mut name_globs, pattern.to_string()),
+                (false, true) => (&mut path_globs, format!("**/{}/**", pattern)),
+                (true, false) => (&mut path_globs, pattern.to_string()),
+                (true, true) => (&mut path_globs, format!("{}/**", pattern)),
+            };
+
+            let glob = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .context(format!(
+                    "Invalid glob pattern in rule {}: {}",
+                    name, pattern
+                ))?;
+            builder.add(glob);
+        }
+
+        let header = header
+            .map(|header| {
+                Regex::new(header).context(format!(
+                    "Invalid header pattern in rule {}: {}",
+                    name, header
+                ))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            name: name.to_string(),
+            name_globs: name_globs.build()?,
+            path_globs: path_globs.build()?,
+            has_paths: !paths.is_empty(),
+            header,
+            minified,
+        })
+    }
+
+    /// パスの条件を満たすかどうか
+    fn matches_path(&self, rel_path: &str, file_name: &str) -> bool {
+        !self.has_paths || self.name_globs.is_match(file_name) || self.path_globs.is_match(rel_path)
+    }
+
+    /// ファイルの内容を確認する必要があるかどうか
+    fn needs_content(&self) -> bool {
+        self.header.is_some() || self.minified
+    }
+
+    /// 先頭部分の条件を満たすかどうか
+    fn matches_content(&self, content: &[u8]) -> bool {
+        if self.minified
+            && !content
+                .split(|&b| b == b'\n')
+                .any(|line| line.len() >= MINIFIED_LINE_LENGTH)
+        {
+            return false;
+        }
+
+        match &self.header {
+            Some(header) => header.is_match(&header_text(content)),
+            None => true,
+        }
+    }
+}
+
+/// 先頭のHEADER_CHECK_LINES行をテキストとして取り出す
+fn header_text(content: &[u8]) -> String {
+    String::from_utf8_lossy(content)
+        .lines()
+        .take(HEADER_CHECK_LINES)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 自動生成ファイルを判定するルールの集合（設定ファイルのルールを組み込みのルールより優先する）
+#[derive(Debug)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::new(&[], &[]).expect("built-in rules are valid")
+    }
+}
+
+impl RuleSet {
+    /// 設定ファイルのルールを追加し、指定された名前のルールを無効にしたルールの集合を作成する
+    pub fn new(custom: &[RuleConfig], disabled: &[String]) -> Result<Self> {
+        let mut rules = Vec::new();
+
+        for config in custom {
+            if config.paths.is_empty() && config.header.is_none() {
+                anyhow::bail!(
+                    "Generated-file rule {} needs `paths` or `header`",
+                    config.name
+                );
+            }
+
+            let paths = config.paths.iter().map(String::as_str).collect::<Vec<_>>();
+            rules.push(Rule::new(
+                &config.name,
+                &paths,
+                config.header.as_deref(),
+                false,
+            )?);
+        }
+
+        for builtin in BUILTIN_RULES {
+            rules.push(Rule::new(
+                builtin.name,
+                builtin.paths,
+                builtin.header,
+                builtin.minified,
+            )?);
+        }
+
+        for name in disabled {
+            if !rules.iter().any(|rule| &rule.name == name) {
+                anyhow::bail!("Unknown generated-file rule: {}", name);
+            }
+        }
+        rules.retain(|rule| !disabled.contains(&rule.name));
+
+        Ok(Self { rules })
+    }
+
+    /// 最初にマッチしたルール名を返す（read_headerは内容の確認が必要になったときに一度だけ呼ぶ）
+    pub fn find<F: FnOnce() -> Option<Vec<u8>>>(
+        &self,
+        rel_path: &Path,
+        read_header: F,
+    ) -> Option<&str> {
+        let rel_path_str = rel_path.to_string_lossy().replace('\\', "/");
+        let file_name = rel_path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut read_header = Some(read_header);
+        let mut content = None;
+
+        for rule in &self.rules {
+            if !rule.matches_path(&rel_path_str, &file_name) {
+                continue;
+            }
+            if !rule.needs_content() {
+                return Some(&rule.name);
+            }
+
+            if let Some(read) = read_header.take() {
+                content = read();
+            }
+            if content.as_deref().is_some_and(|c| rule.matches_content(c)) {
+                return Some(&rule.name);
+            }
+        }
+
+        None
+    }
+}
@@ -1,56 +1,370 @@
 use anyhow::{Context, Result};
-#[cfg(windows)]
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
+use std::io::Write;
 #[cfg(unix)]
 use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+#[cfg(feature = "native-git")]
+mod native;
+
+#[cfg(feature = "native-git")]
+pub use native::NativeBackend;
+
+/// 使用するGitバックエンドの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Read the repository in-process, falling back to the git command on failure
+    #[cfg_attr(feature = "native-git", default)]
+    Native,
+    /// Always run the git command
+    #[cfg_attr(not(feature = "native-git"), default)]
+    Cli,
+}
+
+/// Gitリポジトリを読み込む操作（差分やpathspecの解釈などはgitコマンドで行う）
+pub trait Backend: Send + Sync {
+    /// ディレクトリを含むリポジトリの作業ツリーのルートを取得する
+    fn discover_root(&self, dir: &Path) -> Result<PathBuf>;
+
+    /// インデックスに登録されたファイル一覧を取得する（Gitルートからの絶対パス）
+    fn list_index(&self, git_root: &Path) -> Result<Vec<PathBuf>>;
+
+    /// リビジョンがツリーを指す（存在する）かどうか
+    fn has_revision(&self, git_root: &Path, rev: &str) -> Result<bool>;
+
+    /// リビジョンのツリーに含まれるファイル一覧を取得する（Gitルートからの絶対パス）
+    fn list_tree(&self, git_root: &Path, rev: &str) -> Result<Vec<PathBuf>>;
+
+    /// リビジョンのファイルの内容を読み込む（パスはGitルートからの`/`区切りの相対パス）
+    fn read_blob(&self, git_root: &Path, rev: &str, rel_path: &str) -> Result<Vec<u8>>;
+}
+
+/// プロセス全体で使うバックエンド（最初に使われたときに決まる）
+static BACKEND: OnceLock<Box<dyn Backend>> = OnceLock::new();
+
+/// 解決済みのGitルート（ディレクトリごと）
+static ROOTS: OnceLock<Mutex<HashMap<PathBuf, PathBuf>>> = OnceLock::new();
+
+/// 使用するバックエンドを設定する（Gitの操作を行う前に一度だけ呼ぶ）
+pub fn set_backend(kind: BackendKind) -> Result<()> {
+    let backend: Box<dyn Backend> = match kind {
+        #[cfg(feature = "native-git")]
+        BackendKind::Native => Box::new(Fallback {
+            primary: NativeBackend,
+            secondary: CliBackend,
+        }),
+        #[cfg(not(feature = "native-git"))]
+        BackendKind::Native => {
+            anyhow::bail!("codicat was built without the native-git feature")
+        }
+        BackendKind::Cli => Box::new(CliBackend),
+    };
+
+    if BACKEND.set(backend).is_err() {
+        anyhow::bail!("Git backend is already initialized");
+    }
+    Ok(())
+}
+
+/// 使用するバックエンドを返す（設定されていない場合は既定のバックエンド）
+fn backend() -> &'static dyn Backend {
+    BACKEND
+        .get_or_init(|| {
+            #[cfg(feature = "native-git")]
+            return Box::new(Fallback {
+                primary: NativeBackend,
+                secondary: CliBackend,
+            });
+
+            #[cfg(not(feature = "native-git"))]
+            return Box::new(CliBackend);
+        })
+        .as_ref()
+}
 
 /// Gitのリポジトリルートディレクトリを取得する
+/// （`git -C`と同様にディレクトリ以外のパスはエラーになる。結果はディレクトリごとに記憶する）
 pub fn get_git_root<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
-    let output = Command::new("git")
-        .args([
-            "-C",
-            path.as_ref().to_str().unwrap_or("."),
-            "rev-parse",
-            "--show-toplevel",
-        ])
-        .output()
-        .context("Failed to execute git command")?;
+    let path = path.as_ref();
+    if !path.is_dir() {
+        anyhow::bail!("Not a Git repository: {}", path.display());
+    }
 
-    if !output.status.success() {
-        anyhow::bail!("Not a Git repository: {}", path.as_ref().display());
+    let roots = ROOTS.get_or_init(Default::default);
+    if let Some(root) = roots.lock().ok().and_then(|r| r.get(path).cloned()) {
+        return Ok(root);
+    }
+
+    let root = backend().discover_root(path)?;
+    if let Ok(mut roots) = roots.lock() {
+        roots.insert(path.to_path_buf(), root.clone());
     }
 
-    let root = String::from_utf8(output.stdout)
-        .context("Git output is not valid UTF-8")?
-        .trim()
-        .to_string();
+    Ok(root)
+}
 
-    Ok(PathBuf::from(root))
+/// 最初のバックエンドで失敗した場合に次のバックエンドで再試行するバックエンド
+#[cfg(feature = "native-git")]
+struct Fallback<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+#[cfg(feature = "native-git")]
+impl<P: Backend, S: Backend> Backend for Fallback<P, S> {
+    fn discover_root(&self, dir: &Path) -> Result<PathBuf> {
+        self.primary
+            .discover_root(dir)
+            .or_else(|_| self.secondary.discover_root(dir))
+    }
+
+    fn list_index(&self, git_root: &Path) -> Result<Vec<PathBuf>> {
+        self.primary
+            .list_index(git_root)
+            .or_else(|_| self.secondary.list_index(git_root))
+    }
+
+    fn has_revision(&self, git_root: &Path, rev: &str) -> Result<bool> {
+        self.primary
+            .has_revision(git_root, rev)
+            .or_else(|_| self.secondary.has_revision(git_root, rev))
+    }
+
+    fn list_tree(&self, git_root: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+        self.primary
+            .list_tree(git_root, rev)
+            .or_else(|_| self.secondary.list_tree(git_root, rev))
+    }
+
+    fn read_blob(&self, git_root: &Path, rev: &str, rel_path: &str) -> Result<Vec<u8>> {
+        self.primary
+            .read_blob(git_root, rev, rel_path)
+            .or_else(|_| self.secondary.read_blob(git_root, rev, rel_path))
+    }
+}
+
+/// gitコマンドを実行するバックエンド
+pub struct CliBackend;
+
+impl Backend for CliBackend {
+    fn discover_root(&self, dir: &Path) -> Result<PathBuf> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                dir.to_str().unwrap_or("."),
+                "rev-parse",
+                "--show-toplevel",
+            ])
+            .output()
+            .context("Failed to execute git command")?;
+
+        if !output.status.success() {
+            anyhow::bail!("Not a Git repository: {}", dir.display());
+        }
+
+        let root = String::from_utf8(output.stdout)
+            .context("Git output is not valid UTF-8")?
+            .trim()
+            .to_string();
+
+        Ok(PathBuf::from(root))
+    }
+
+    fn list_index(&self, git_root: &Path) -> Result<Vec<PathBuf>> {
+        ls_files(git_root, git_root, &[], &[])
+    }
+
+    fn has_revision(&self, git_root: &Path, rev: &str) -> Result<bool> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                git_root.to_str().unwrap_or("."),
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{tree}}", rev),
+            ])
+            .output()
+            .context("Failed to execute git command")?;
+
+        Ok(output.status.success())
+    }
+
+    fn list_tree(&self, git_root: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                git_root.to_str().unwrap_or("."),
+                "ls-tree",
+                "-r",
+                "-z",
+                rev,
+            ])
+            .output()
+            .context("Failed to execute git command")?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to list files in revision: {}", rev);
+        }
+
+        // 各エントリは「モード 種類 オブジェクト\tパス\0」の形式（サブモジュールなどblob以外は除外する）
+        let mut files = Vec::new();
+        for entry in output.stdout.split(|&b| b == 0).filter(|e| !e.is_empty()) {
+            let Some(tab) = entry.iter().position(|&b| b == b'\t') else {
+                continue;
+            };
+
+            let is_blob = entry[..tab].split(|&b| b == b' ').nth(1) == Some(b"blob".as_slice());
+            if is_blob {
+                files.push(git_root.join(path_from_bytes(&entry[tab + 1..])?));
+            }
+        }
+
+        Ok(files)
+    }
+
+    fn read_blob(&self, git_root: &Path, rev: &str, rel_path: &str) -> Result<Vec<u8>> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                git_root.to_str().unwrap_or("."),
+                "cat-file",
+                "blob",
+                &format!("{}:{}", rev, rel_path),
+            ])
+            .output()
+            .context("Failed to execute git command")?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to read {} at revision {}", rel_path, rev);
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+/// gitコマンドに渡す対象範囲
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pathspec {
+    /// gitコマンドを実行するディレクトリ（相対パスのpathspecはここを基準に解釈される）
+    pub dir: PathBuf,
+    /// パスやpathspec（`:!*.snap`など）。空の場合はリポジトリ全体を対象にする
+    pub specs: Vec<OsString>,
+}
+
+impl Pathspec {
+    /// 実行ディレクトリとpathspecから対象範囲を作成する
+    pub fn new<P: Into<PathBuf>>(dir: P, specs: Vec<OsString>) -> Self {
+        Self {
+            dir: dir.into(),
+            specs,
+        }
+    }
 }
 
 /// 指定ディレクトリ以下のGit管理下のファイル一覧を取得する
 pub fn list_git_tracked_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
+    list_files(&Pathspec::new(dir.as_ref(), Vec::new()), &[])
+}
+
+/// pathspecに一致するGit管理下のファイル一覧を取得する
+pub fn list_git_tracked_files_in(pathspec: &Pathspec) -> Result<Vec<PathBuf>> {
+    list_files(pathspec, &[])
+}
+
+/// ツリービューで未追跡ファイルに付ける記号
+pub const UNTRACKED_MARKER: &str = "?";
+
+/// pathspecに一致する未追跡（かつ無視されていない）ファイル一覧を取得する
+pub fn list_untracked_files(pathspec: &Pathspec) -> Result<Vec<PathBuf>> {
+    list_files(pathspec, &["--others", "--exclude-standard"])
+}
+
+/// `git ls-files`の結果をGitルートからの絶対パスとして取得する
+fn list_files(pathspec: &Pathspec, extra_args: &[&str]) -> Result<Vec<PathBuf>> {
     // 最初にGitリポジトリのルートディレクトリを取得
-    let git_root = get_git_root(&dir)?;
+    let git_root = get_git_root(&pathspec.dir)?;
+
+    // Git管理下のファイルは、pathspecがパスだけの場合はインデックスの一覧を絞り込んで求める
+    // （pathspecがない場合はサブディレクトリからの実行でも全ファイルを対象にする）
+    if extra_args.is_empty() {
+        if let Some(paths) = plain_paths(pathspec, &git_root) {
+            let files = backend().list_index(&git_root)?;
+            return Ok(filter_paths(files, &paths));
+        }
+    }
 
-    // サブディレクトリからの実行でも全ファイルを取得するため、
-    // Gitリポジトリのルートディレクトリから実行する
+    if pathspec.specs.is_empty() {
+        return ls_files(&git_root, &git_root, extra_args, &[]);
+    }
+
+    ls_files(&pathspec.dir, &git_root, extra_args, &pathspec.specs)
+}
+
+/// pathspecがGitルート配下の絶対パスだけからなる場合はそのパスを返す
+/// （除外やglobなどの指定を含む場合は、解釈をgitコマンドに任せるためNone）
+fn plain_paths(pathspec: &Pathspec, git_root: &Path) -> Option<Vec<PathBuf>> {
+    pathspec
+        .specs
+        .iter()
+        .map(|spec| {
+            let path = Path::new(spec);
+            let is_glob = spec.to_str().is_none_or(|s| s.contains(['*', '?', '[']));
+            (path.is_absolute() && path.starts_with(git_root) && !is_glob)
+                .then(|| path.to_path_buf())
+        })
+        .collect()
+}
+
+/// 指定したパスのいずれか（またはその配下）に一致するファイルだけを残す（パスがない場合はすべて残す）
+fn filter_paths(files: Vec<PathBuf>, paths: &[PathBuf]) -> Vec<PathBuf> {
+    if paths.is_empty() {
+        return files;
+    }
+
+    files
+        .into_iter()
+        .filter(|file| paths.iter().any(|path| file.starts_with(path)))
+        .collect()
+}
+
+/// `git ls-files`を実行し、結果をGitルートからの絶対パスとして取得する
+fn ls_files(
+    run_dir: &Path,
+    git_root: &Path,
+    extra_args: &[&str],
+    specs: &[OsString],
+) -> Result<Vec<PathBuf>> {
     #[cfg(unix)]
     let output = Command::new("git")
-        .args(["-C", git_root.to_str().unwrap_or("."), "ls-files", "-z"])
+        .args(["-C", run_dir.to_str().unwrap_or("."), "ls-files", "-z"])
+        .args(["--full-name"])
+        .args(extra_args)
+        .arg("--")
+        .args(specs)
         .output()
         .context("Failed to execute git command")?;
 
     #[cfg(windows)]
     let output = Command::new("git")
-        .args(["-C", git_root.to_str().unwrap_or("."), "ls-files"])
+        .args(["-C", run_dir.to_str().unwrap_or("."), "ls-files"])
+        .args(["--full-name"])
+        .args(extra_args)
+        .arg("--")
+        .args(specs)
         .output()
         .context("Failed to execute git command")?;
 
     if !output.status.success() {
-        anyhow::bail!("Failed to list Git-tracked files");
+        anyhow::bail!("git ls-files failed in: {}", git_root.display());
     }
 
     #[cfg(unix)]
@@ -93,3 +407,479 @@ pub fn list_git_tracked_files<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
         Ok(files)
     }
 }
+
+/// 差分の比較対象
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpec {
+    /// 指定したリビジョンから作業ツリーまでの変更
+    Since(String),
+    /// リビジョン範囲（`base..head` / `base...head`）の変更
+    Range(String),
+    /// ステージ済みの変更（HEADとインデックスの差分）
+    Staged,
+    /// 未ステージの変更（インデックスと作業ツリーの差分）
+    Unstaged,
+}
+
+impl DiffSpec {
+    /// `git diff`に渡す引数を返す
+    fn args(&self) -> Vec<&str> {
+        match self {
+            DiffSpec::Since(rev) => vec![rev.as_str()],
+            DiffSpec::Range(range) => vec![range.as_str()],
+            DiffSpec::Staged => vec!["--cached"],
+            DiffSpec::Unstaged => Vec::new(),
+        }
+    }
+
+    /// 変更後の内容を読み込むリビジョン（リビジョン範囲のみ。それ以外は作業ツリーから読み込む）
+    pub fn head(&self) -> Option<&str> {
+        let DiffSpec::Range(range) = self else {
+            return None;
+        };
+
+        // `base..`のように変更後を省略した場合はHEADとの比較になる
+        let (_, head) = range.split_once("...").or_else(|| range.split_once(".."))?;
+        Some(if head.is_empty() { "HEAD" } else { head })
+    }
+
+    /// 変更後の内容を読み込むスナップショットを作成する（作業ツリーから読み込む場合はNone）
+    pub fn snapshot<P: AsRef<Path>>(&self, dir: P) -> Result<Option<Snapshot>> {
+        match self {
+            // ステージ済みの変更はインデックスの内容を表示する
+            DiffSpec::Staged => Snapshot::index(dir).map(Some),
+            _ => self.head().map(|rev| Snapshot::new(dir, rev)).transpose(),
+        }
+    }
+}
+
+/// 変更の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    TypeChanged,
+}
+
+impl ChangeStatus {
+    /// `git diff --name-status`の状態文字から変換する
+    fn from_code(code: &str) -> Option<Self> {
+        match code.chars().next()? {
+            'A' => Some(ChangeStatus::Added),
+            'M' => Some(ChangeStatus::Modified),
+            'D' => Some(ChangeStatus::Deleted),
+            'R' => Some(ChangeStatus::Renamed),
+            'C' => Some(ChangeStatus::Copied),
+            'T' => Some(ChangeStatus::TypeChanged),
+            _ => None,
+        }
+    }
+
+    /// ツリーや出力に表示する記号を返す
+    pub fn marker(self) -> &'static str {
+        match self {
+            ChangeStatus::Added => "A",
+            ChangeStatus::Modified => "M",
+            ChangeStatus::Deleted => "D",
+            ChangeStatus::Renamed => "R",
+            ChangeStatus::Copied => "C",
+            ChangeStatus::TypeChanged => "T",
+        }
+    }
+}
+
+/// 変更されたファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// 変更後のファイルパス（絶対パス）
+    pub path: PathBuf,
+    /// 名前変更・コピー元のファイルパス（絶対パス）
+    pub old_path: Option<PathBuf>,
+    /// 変更の種類
+    pub status: ChangeStatus,
+}
+
+/// 比較対象の間で変更された、pathspecに一致するファイル一覧を取得する
+pub fn list_changed_files(pathspec: &Pathspec, spec: &DiffSpec) -> Result<Vec<ChangedFile>> {
+    let git_root = get_git_root(&pathspec.dir)?;
+
+    // 出力されるパスは常にGitルートからの相対パスにする
+    let mut args = vec![
+        "-C",
+        pathspec.dir.to_str().unwrap_or("."),
+        "diff",
+        "-M",
+        "--name-status",
+        "--no-relative",
+        "-z",
+    ];
+    args.extend(spec.args());
+
+    let output = Command::new("git")
+        .args(&args)
+        .arg("--")
+        .args(&pathspec.specs)
+        .output()
+        .context("Failed to execute git command")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to list changed files: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // -z オプションでは「状態\0パス\0」、名前変更・コピーは「状態\0元のパス\0新しいパス\0」の形式になる
+    let mut fields = output.stdout.split(|&b| b == 0).filter(|f| !f.is_empty());
+    let mut changes = Vec::new();
+
+    while let Some(code) = fields.next() {
+        let code = String::from_utf8_lossy(code);
+        let status = ChangeStatus::from_code(&code)
+            .with_context(|| format!("Unknown change status: {}", code))?;
+
+        let first = fields.next().context("Unexpected git diff output")?;
+        let (old_path, path) = match status {
+            ChangeStatus::Renamed | ChangeStatus::Copied => {
+                let second = fields.next().context("Unexpected git diff output")?;
+                (Some(git_root.join(path_from_bytes(first)?)), second)
+            }
+            _ => (None, first),
+        };
+
+        changes.push(ChangedFile {
+            path: git_root.join(path_from_bytes(path)?),
+            old_path,
+            status,
+        });
+    }
+
+    Ok(changes)
+}
+
+/// 変更されたファイルの統一diff形式の差分を取得する（Gitルートは呼び出し側で一度だけ解決する）
+pub fn diff_file<P: AsRef<Path>>(
+    git_root: P,
+    spec: &DiffSpec,
+    change: &ChangedFile,
+) -> Result<String> {
+    let git_root = git_root.as_ref();
+
+    let mut args = vec!["-C", git_root.to_str().unwrap_or("."), "diff", "-M"];
+    args.extend(spec.args());
+    args.push("--");
+    if let Some(old_path) = &change.old_path {
+        args.push(old_path.to_str().unwrap_or_default());
+    }
+    args.push(change.path.to_str().unwrap_or_default());
+
+    let output = Command::new("git")
+        .args(&args)
+        .output()
+        .context("Failed to execute git command")?;
+
+    if !output.status.success() {
+        anyhow::bail!("Failed to get diff: {}", change.path.display());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// ファイルごとのGit属性（属性名と値。値は`set`・`unset`・または`attr=value`の値）
+pub type Attributes = HashMap<PathBuf, HashMap<String, String>>;
+
+/// `git check-attr`で`.gitattributes`から指定した属性を取得する（未指定の属性は含めない）
+/// （スナップショットを指定した場合は作業ツリーではなく、そのリビジョン・インデックスの`.gitattributes`を使う）
+pub fn check_attributes(
+    git_root: &Path,
+    files: &[PathBuf],
+    names: &[&str],
+    snapshot: Option<&Snapshot>,
+) -> Result<Attributes> {
+    let mut attributes = Attributes::new();
+    if files.is_empty() || names.is_empty() {
+        return Ok(attributes);
+    }
+
+    let mut input = Vec::new();
+    for file in files {
+        let rel_path = file.strip_prefix(git_root).unwrap_or(file);
+        input.extend_from_slice(&path_to_bytes(rel_path));
+        input.push(0);
+    }
+
+    let output = match snapshot {
+        None => run_check_attr(git_root, &[], names, &input, None)?,
+        Some(snapshot) if snapshot.rev.is_empty() => {
+            run_check_attr(git_root, &["--cached"], names, &input, None)?
+        }
+        Some(snapshot) => {
+            let output =
+                run_check_attr(git_root, &["--source", &snapshot.rev], names, &input, None)?;
+            if output.status.success()
+                || !String::from_utf8_lossy(&output.stderr).contains("unknown option")
+            {
+                output
+            } else {
+                // `--source`に対応していないGit（2.40未満）では、リビジョンを読み込んだ一時的なインデックスを使う
+                check_attr_with_index(git_root, &snapshot.rev, names, &input)?
+            }
+        }
+    };
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git check-attr failed in {}: {}",
+            git_root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    // 出力は「パス\0属性名\0値\0」の繰り返し
+    let fields = output.stdout.split(|&b| b == 0).collect::<Vec<_>>();
+    for entry in fields.chunks_exact(3) {
+        let value = String::from_utf8_lossy(entry[2]).to_string();
+        if value == "unspecified" {
+            continue;
+        }
+
+        attributes
+            .entry(git_root.join(path_from_bytes(entry[0])?))
+            .or_default()
+            .insert(String::from_utf8_lossy(entry[1]).to_string(), value);
+    }
+
+    Ok(attributes)
+}
+
+/// リビジョンを一時的なインデックスに読み込み、そのインデックスの`.gitattributes`で属性を取得する
+fn check_attr_with_index(
+    git_root: &Path,
+    rev: &str,
+    names: &[&str],
+    input: &[u8],
+) -> Result<Output> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let index = std::env::temp_dir().join(format!(
+        "codicat-index-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let read_tree = Command::new("git")
+        .args(["-C", git_root.to_str().unwrap_or("."), "read-tree", rev])
+        .env("GIT_INDEX_FILE", &index)
+        .output()
+        .context("Failed to execute git command");
+    let output = match read_tree {
+        Ok(read_tree) if !read_tree.status.success() => Err(anyhow::anyhow!(
+            "Failed to read revision {}: {}",
+            rev,
+            String::from_utf8_lossy(&read_tree.stderr).trim()
+        )),
+        Ok(_) => run_check_attr(git_root, &["--cached"], names, input, Some(&index)),
+        Err(err) => Err(err),
+    };
+    let _ = std::fs::remove_file(&index);
+
+    output
+}
+
+/// `git check-attr -z --stdin`を実行する（`index`を指定した場合はそのインデックスを使う）
+fn run_check_attr(
+    git_root: &Path,
+    args: &[&str],
+    names: &[&str],
+    input: &[u8],
+    index: Option<&Path>,
+) -> Result<Output> {
+    let mut command = Command::new("git");
+    command
+        .args([
+            "-C",
+            git_root.to_str().unwrap_or("."),
+            "check-attr",
+            "-z",
+            "--stdin",
+        ])
+        .args(args)
+        .args(names)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let mut child = command.spawn().context("Failed to execute git command")?;
+
+    // 出力を読みながら書き込まないとパイプが詰まるため、入力は別スレッドで書き込む
+    let mut stdin = child.stdin.take().context("Failed to open git stdin")?;
+    let input = input.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child
+        .wait_with_output()
+        .context("Failed to execute git command")?;
+    let written = writer
+        .join()
+        .map_err(|_| anyhow::anyhow!("Failed to write to git stdin"))?;
+    // 未対応のオプションなどで失敗した場合は入力を読まずに終了するため、成功した場合のみ書き込みを確認する
+    if output.status.success() {
+        written.context("Failed to write to git stdin")?;
+    }
+
+    Ok(output)
+}
+
+/// gitに渡すパスのバイト列を取得する
+fn path_to_bytes(path: &Path) -> Vec<u8> {
+    #[cfg(unix)]
+    {
+        path.as_os_str().as_bytes().to_vec()
+    }
+
+    #[cfg(windows)]
+    {
+        path.to_string_lossy().replace('\\', "/").into_bytes()
+    }
+}
+
+/// gitが出力したパスのバイト列をPathBufに変換する
+fn path_from_bytes(bytes: &[u8]) -> Result<PathBuf> {
+    #[cfg(unix)]
+    {
+        Ok(PathBuf::from(std::ffi::OsStr::from_bytes(bytes)))
+    }
+
+    #[cfg(windows)]
+    {
+        let path = String::from_utf8(bytes.to_vec()).context("Git output is not valid UTF-8")?;
+        Ok(PathBuf::from(OsString::from(path)))
+    }
+}
+
+/// 特定のリビジョンのスナップショット（作業ツリーを変更せずにファイルを読み込む）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Gitリポジトリのルートディレクトリ
+    pub git_root: PathBuf,
+    /// 対象のリビジョン（コミット・タグ・ブランチなど。空の場合はインデックス）
+    pub rev: String,
+}
+
+impl Snapshot {
+    /// リビジョンが存在することを確認してスナップショットを作成する
+    pub fn new<P: AsRef<Path>>(dir: P, rev: &str) -> Result<Self> {
+        let git_root = get_git_root(&dir)?;
+
+        if !backend().has_revision(&git_root, rev)? {
+            anyhow::bail!("Unknown revision: {}", rev);
+        }
+
+        Ok(Self {
+            git_root,
+            rev: rev.to_string(),
+        })
+    }
+
+    /// インデックス（ステージ済みの内容）のスナップショットを作成する
+    /// （`:<path>`でインデックスのblobを読み込む。ファイル一覧は変更一覧から取得する）
+    pub fn index<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        Ok(Self {
+            git_root: get_git_root(&dir)?,
+            rev: String::new(),
+        })
+    }
+
+    /// スナップショットに含まれるすべてのファイル一覧を取得する
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        if self.rev.is_empty() {
+            backend().list_index(&self.git_root)
+        } else {
+            backend().list_tree(&self.git_root, &self.rev)
+        }
+    }
+
+    /// スナップショットに含まれる、pathspecに一致するファイル一覧を取得する
+    pub fn list_files(&self, pathspec: &Pathspec) -> Result<Vec<PathBuf>> {
+        let files = backend().list_tree(&self.git_root, &self.rev)?;
+
+        if let Some(paths) = plain_paths(pathspec, &self.git_root) {
+            return Ok(filter_paths(files, &paths));
+        }
+
+        let matching = self.matching_files(pathspec)?;
+        Ok(files
+            .into_iter()
+            .filter(|file| matching.contains(file))
+            .collect())
+    }
+
+    /// pathspecに一致するファイルを取得する
+    /// （`git ls-tree`は除外などのpathspecに対応していないため、空のツリーとの差分として求める）
+    fn matching_files(&self, pathspec: &Pathspec) -> Result<HashSet<PathBuf>> {
+        let output = Command::new("git")
+            .args([
+                "-C",
+                self.git_root.to_str().unwrap_or("."),
+                "hash-object",
+                "-t",
+                "tree",
+                "--stdin",
+            ])
+            .stdin(Stdio::null())
+            .output()
+            .context("Failed to execute git command")?;
+
+        if !output.status.success() {
+            anyhow::bail!("Failed to resolve the empty tree");
+        }
+        let empty_tree = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        let output = Command::new("git")
+            .args([
+                "-C",
+                pathspec.dir.to_str().unwrap_or("."),
+                "diff-tree",
+                "-r",
+                "-z",
+                "--name-only",
+                "--no-renames",
+                &empty_tree,
+                &self.rev,
+                "--",
+            ])
+            .args(&pathspec.specs)
+            .output()
+            .context("Failed to execute git command")?;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "Failed to match paths in revision {}: {}",
+                self.rev,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        output
+            .stdout
+            .split(|&b| b == 0)
+            .filter(|path| !path.is_empty())
+            .map(|path| Ok(self.git_root.join(path_from_bytes(path)?)))
+            .collect()
+    }
+
+    /// スナップショット上のファイルの内容を読み込む
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
+        let rel_path = path
+            .as_ref()
+            .strip_prefix(&self.git_root)
+            .unwrap_or(path.as_ref())
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        backend().read_blob(&self.git_root, &self.rev, &rel_path)
+    }
+}
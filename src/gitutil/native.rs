@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::{path_from_bytes, Backend};
+
+thread_local! {
+    /// 開いたリポジトリ（Gitルートごと、gixのリポジトリはスレッド間で共有できないためスレッドごとに持つ）
+    static REPOS: RefCell<HashMap<PathBuf, gix::Repository>> = RefCell::new(HashMap::new());
+}
+
+/// gitoxideでリポジトリをプロセス内で読み込むバックエンド
+#[derive(Debug, Default)]
+pub struct NativeBackend;
+
+impl NativeBackend {
+    /// Gitルートのリポジトリを開く（一度開いたリポジトリは再利用する）
+    fn open(&self, git_root: &Path) -> Result<gix::Repository> {
+        if let Some(repo) = REPOS.with(|repos| repos.borrow().get(git_root).cloned()) {
+            return Ok(repo);
+        }
+
+        let repo = gix::open(git_root)
+            .context(format!("Failed to open repository: {}", git_root.display()))?;
+        REPOS.with(|repos| {
+            repos
+                .borrow_mut()
+                .insert(git_root.to_path_buf(), repo.clone());
+        });
+        Ok(repo)
+    }
+
+    /// リビジョンが指すツリーを取得する
+    fn tree<'repo>(&self, repo: &'repo gix::Repository, rev: &str) -> Result<gix::Tree<'repo>> {
+        let tree = repo
+            .rev_parse_single(rev)
+            .context(format!("Unknown revision: {}", rev))?
+            .object()?
+            .peel_to_tree()?;
+        Ok(tree)
+    }
+}
+
+impl Backend for NativeBackend {
+    fn discover_root(&self, dir: &Path) -> Result<PathBuf> {
+        let repo =
+            gix::discover(dir).context(format!("Not a Git repository: {}", dir.display()))?;
+        let workdir = repo
+            .workdir()
+            .context(format!("Repository has no working tree: {}", dir.display()))?;
+
+        // gitコマンドと同じくシンボリックリンクを解決した絶対パスにする
+        let root = workdir.canonicalize()?;
+
+        REPOS.with(|repos| {
+            repos.borrow_mut().entry(root.clone()).or_insert(repo);
+        });
+
+        Ok(root)
+    }
+
+    fn list_index(&self, git_root: &Path) -> Result<Vec<PathBuf>> {
+        let repo = self.open(git_root)?;
+        let index = repo.index_or_empty()?;
+
+        // スパースインデックスはディレクトリ単位のエントリを含むため、gitコマンドに任せる
+        if index.is_sparse() {
+            anyhow::bail!("Sparse index is not supported: {}", git_root.display());
+        }
+
+        let mut files = index
+            .entries()
+            .iter()
+            .map(|entry| Ok(git_root.join(path_from_bytes(entry.path(&index))?)))
+            .collect::<Result<Vec<_>>>()?;
+
+        // 競合中のファイルはステージごとにエントリがある
+        files.dedup();
+        Ok(files)
+    }
+
+    fn has_revision(&self, git_root: &Path, rev: &str) -> Result<bool> {
+        // 解釈できないリビジョンはエラーにして、gitコマンドで確認させる
+        let repo = self.open(git_root)?;
+        self.tree(&repo, rev)?;
+        Ok(true)
+    }
+
+    fn list_tree(&self, git_root: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+        let repo = self.open(git_root)?;
+        let tree = self.tree(&repo, rev)?;
+
+        let mut recorder = gix::traverse::tree::Recorder::default();
+        tree.traverse().breadthfirst(&mut recorder)?;
+
+        // `git ls-tree -r`と同じく、シンボリックリンクを含むblobのみをパス順に返す
+        let mut entries = recorder
+            .records
+            .into_iter()
+            .filter(|entry| entry.mode.is_blob_or_symlink())
+            .map(|entry| entry.filepath)
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        entries
+            .iter()
+            .map(|path| Ok(git_root.join(path_from_bytes(path)?)))
+            .collect()
+    }
+
+    fn read_blob(&self, git_root: &Path, rev: &str, rel_path: &str) -> Result<Vec<u8>> {
+        let repo = self.open(git_root)?;
+        let spec = format!("{}:{}", rev, rel_path);
+
+        let object = repo
+            .rev_parse_single(spec.as_str())
+            .context(format!("Failed to read {} at revision {}", rel_path, rev))?
+            .object()?;
+
+        if object.kind != gix::object::Kind::Blob {
+            anyhow::bail!("Failed to read {} at revision {}", rel_path, rev);
+        }
+
+        Ok(object.detach().data)
+    }
+}
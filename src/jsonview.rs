@@ -0,0 +1,279 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+use crate::budget::Omitted;
+use crate::fileview::{self, FileContent, ViewOptions};
+use crate::tokens::{Encoding, TokenCounter};
+use crate::treeview::TreeNode;
+
+/// ファイル1件分のレコード
+#[derive(Debug, Serialize)]
+pub struct FileRecord {
+    /// Gitルートからの相対パス
+    pub path: String,
+    /// ファイルサイズ（バイト）
+    pub size: u64,
+    /// ファイル全体の行数
+    pub lines: usize,
+    /// 表示範囲を指定した場合の1始まりの開始行と終了行
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<(usize, usize)>,
+    /// バイナリファイルかどうか
+    pub binary: bool,
+    /// UTF-8以外の文字コードから変換した場合はその名前
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<&'static str>,
+    /// max_linesによって切り詰められたかどうか
+    pub truncated: bool,
+    /// max_linesによって省略した行の範囲（1始まりの開始行と終了行）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub omitted_lines: Vec<(usize, usize)>,
+    /// サイズの上限によって内容を省略したかどうか
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+    /// max_line_lengthによって短縮した行数
+    #[serde(skip_serializing_if = "is_zero")]
+    pub clipped_lines: usize,
+    /// 出力した内容のトークン数
+    pub tokens: usize,
+    /// 出力した内容（バイナリファイルの場合はなし）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// 差分モードでの変更の種類（A/M/D/R/C/T）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<&'static str>,
+    /// 差分モードでの統一diff形式の差分
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+impl FileRecord {
+    /// 読み込んだファイルからレコードを作成する
+    pub fn new(file: &FileContent, counter: &TokenCounter) -> Self {
+        let content = (!file.is_binary && !file.skipped).then(|| file.text());
+        let tokens = content.as_deref().map_or(0, |text| counter.count(text));
+
+        Self {
+            path: file.path.clone(),
+            size: file.size,
+            lines: file.total_lines,
+            range: file.range,
+            binary: file.is_binary,
+            encoding: file.encoding,
+            truncated: file.is_truncated(),
+            omitted_lines: file.omitted.clone(),
+            skipped: file.skipped,
+            clipped_lines: file.clipped_lines,
+            tokens,
+            content,
+            status: None,
+            diff: None,
+        }
+    }
+
+    /// 作業ツリーに存在しない（削除された）ファイルのレコードを作成する
+    pub fn missing<P: AsRef<Path>>(path: P, options: &ViewOptions) -> Self {
+        Self {
+            path: fileview::display_path(path.as_ref(), options.git_root.as_deref()),
+            size: 0,
+            lines: 0,
+            range: None,
+            binary: false,
+            encoding: None,
+            truncated: false,
+            omitted_lines: Vec::new(),
+            skipped: false,
+            clipped_lines: 0,
+            tokens: 0,
+            content: None,
+            status: None,
+            diff: None,
+        }
+    }
+
+    /// 差分モードの変更の種類と差分を設定する（keep_contentがfalseなら内容は含めない）
+    pub fn with_diff(
+        mut self,
+        status: &'static str,
+        diff: Option<String>,
+        keep_content: bool,
+        counter: &TokenCounter,
+    ) -> Self {
+        if !keep_content {
+            self.content = None;
+        }
+
+        self.status = Some(status);
+        self.diff = diff;
+        self.tokens = [&self.content, &self.diff]
+            .into_iter()
+            .flatten()
+            .map(|text| counter.count(text))
+            .sum();
+        self
+    }
+
+    /// 出力全体のバイト数の上限を超えるため、内容と差分を省略する
+    pub fn skip(&mut self) {
+        self.skipped = true;
+        self.content = None;
+        self.diff = None;
+        self.tokens = 0;
+    }
+
+    /// 出力する内容と差分のバイト数
+    pub fn content_bytes(&self) -> u64 {
+        [&self.content, &self.diff]
+            .into_iter()
+            .flatten()
+            .map(|text| text.len() as u64)
+            .sum()
+    }
+
+    /// ファイルを読み込んでレコードを作成する
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        options: &ViewOptions,
+        counter: &TokenCounter,
+    ) -> Result<Self> {
+        Ok(Self::new(&fileview::load_file(path, options)?, counter))
+    }
+}
+
+/// 出力全体の集計
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    /// ファイル数
+    pub files: usize,
+    /// 行数の合計
+    pub lines: usize,
+    /// サイズの合計（バイト）
+    pub size: u64,
+    /// トークン数の合計
+    pub tokens: usize,
+    /// トークン数の計算に使ったエンコーディング
+    pub tokenizer: &'static str,
+    /// バイナリファイル数
+    pub binary_files: usize,
+    /// 切り詰められたファイル数
+    pub truncated_files: usize,
+    /// トークン予算により省略されたファイル数
+    pub omitted_files: usize,
+    /// サイズの上限により内容を省略したファイル数
+    pub skipped_files: usize,
+    /// 長い行を短縮したファイル数
+    pub clipped_files: usize,
+}
+
+impl Summary {
+    /// レコードを集計に加える
+    fn add(&mut self, record: &FileRecord) {
+        self.files += 1;
+        self.lines += record.lines;
+        self.size += record.size;
+        self.tokens += record.tokens;
+        self.binary_files += usize::from(record.binary);
+        self.truncated_files += usize::from(record.truncated);
+        self.skipped_files += usize::from(record.skipped);
+        self.clipped_files += usize::from(record.clipped_lines > 0);
+    }
+}
+
+/// JSONドキュメント全体
+#[derive(Debug, Serialize)]
+struct Document<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tree: Option<&'a TreeNode>,
+    files: Vec<FileRecord>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    omitted: &'a [Omitted],
+    summary: Summary,
+}
+
+/// JSON Linesの1行分のレコード
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record<'a> {
+    Tree(&'a TreeNode),
+    File(FileRecord),
+    Omitted(&'a Omitted),
+    Summary(Summary),
+}
+
+/// ツリーとファイル一覧を1つのJSONドキュメントとして出力する
+pub fn write_json<W: Write>(
+    writer: &mut W,
+    tree: Option<&TreeNode>,
+    records: Vec<FileRecord>,
+    omitted: &[Omitted],
+    encoding: Encoding,
+) -> Result<()> {
+    let mut summary = Summary {
+        tokenizer: encoding.name(),
+        omitted_files: omitted.len(),
+        ..Default::default()
+    };
+    for record in &records {
+        summary.add(record);
+    }
+
+    let document = Document {
+        tree,
+        files: records,
+        omitted,
+        summary,
+    };
+
+    serde_json::to_writer_pretty(&mut *writer, &document)?;
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+/// ツリー・ファイル・集計を1行1レコードのJSON Linesとして出力する（ファイルは読み込むたびに書き込む）
+pub fn write_jsonl<W: Write, I: IntoIterator<Item = Result<FileRecord>>>(
+    writer: &mut W,
+    tree: Option<&TreeNode>,
+    records: I,
+    omitted: &[Omitted],
+    encoding: Encoding,
+) -> Result<()> {
+    if let Some(tree) = tree {
+        write_record(writer, &Record::Tree(tree))?;
+        writer.flush()?;
+    }
+
+    let mut summary = Summary {
+        tokenizer: encoding.name(),
+        omitted_files: omitted.len(),
+        ..Default::default()
+    };
+    for record in records {
+        let record = record?;
+        summary.add(&record);
+        write_record(writer, &Record::File(record))?;
+        writer.flush()?;
+    }
+
+    for file in omitted {
+        write_record(writer, &Record::Omitted(file))?;
+    }
+
+    write_record(writer, &Record::Summary(summary))?;
+
+    Ok(())
+}
+
+/// レコードを1行のJSONとして出力する
+fn write_record<W: Write>(writer: &mut W, record: &Record) -> Result<()> {
+    serde_json::to_writer(&mut *writer, record)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// serdeで0の項目を省略するための判定
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
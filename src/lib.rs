@@ -1,5 +1,15 @@
+pub mod budget;
+pub mod charset;
 pub mod cli;
+pub mod config;
 pub mod filefilter;
 pub mod fileview;
+pub mod format;
+pub mod generated;
 pub mod gitutil;
+pub mod jsonview;
+pub mod limits;
+pub mod parallel;
+pub mod region;
+pub mod tokens;
 pub mod treeview;
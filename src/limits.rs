@@ -0,0 +1,185 @@
+use anyhow::Result;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::format::{self, OutputFormat};
+
+/// サイズの単位（1024の累乗）
+const UNITS: [(&str, u64); 3] = [("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+
+/// バイト数（`1048576`のほか`512K`・`10M`・`1G`のような単位付きの指定を受け付ける）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let digits = trimmed
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(trimmed.len());
+        let (number, unit) = trimmed.split_at(digits);
+
+        // `K`・`KB`・`KiB`はいずれも1024バイトとして扱う
+        let unit = unit.trim().to_ascii_uppercase();
+        let unit = unit
+            .strip_suffix("IB")
+            .or_else(|| unit.strip_suffix('B'))
+            .unwrap_or(&unit);
+        let multiplier = match unit {
+            "" => 1,
+            _ => match UNITS.iter().find(|(name, _)| *name == unit) {
+                Some((_, multiplier)) => *multiplier,
+                None => anyhow::bail!("Invalid size: {} (use a number with K, M or G)", s),
+            },
+        };
+
+        let Ok(number) = number.parse::<u64>() else {
+            anyhow::bail!("Invalid size: {} (use a number with K, M or G)", s);
+        };
+        match number.checked_mul(multiplier) {
+            Some(bytes) => Ok(ByteSize(bytes)),
+            None => anyhow::bail!("Size is too large: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 割り切れる最大の単位で表示する（指定した書式に戻せるようにする）
+        match UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 > 0 && self.0.is_multiple_of(*multiplier))
+        {
+            Some((name, multiplier)) => write!(f, "{}{}", self.0 / multiplier, name),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // 設定ファイルでは`max-file-size = 1048576`と`max-file-size = "1M"`のどちらも受け付ける
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+/// ファイルサイズを読みやすい単位で表す（`812 B`・`1.5 KiB`・`40.0 MiB`）
+pub fn human_size(bytes: u64) -> String {
+    match UNITS.iter().find(|(_, multiplier)| bytes >= *multiplier) {
+        Some((name, multiplier)) => {
+            format!("{:.1} {}iB", bytes as f64 / *multiplier as f64, name)
+        }
+        None => format!("{} B", bytes),
+    }
+}
+
+/// サイズの上限により内容を省略した、または長い行を短縮したファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limited {
+    /// Gitルートからの相対パス
+    pub path: String,
+    /// 省略・短縮の内容と理由
+    pub reason: String,
+}
+
+/// 出力全体のバイト数の上限
+#[derive(Debug, Clone, Copy)]
+pub struct TotalBytes {
+    /// 上限（なしの場合は無制限）
+    limit: Option<u64>,
+    /// これまでに出力したバイト数
+    used: u64,
+    /// 上限を超えるファイルがあったかどうか（以降のファイルはすべて省略する）
+    exhausted: bool,
+}
+
+impl TotalBytes {
+    /// 既に出力したバイト数を含めて上限を設定する
+    pub fn new(limit: Option<u64>, used: u64) -> Self {
+        Self {
+            limit,
+            used,
+            exhausted: false,
+        }
+    }
+
+    /// 上限に収まる場合は出力したものとして数え、収まらない場合はfalseを返す
+    /// （出力が途中で抜けないよう、一度上限を超えた後は収まる大きさのファイルも受け付けない）
+    pub fn take(&mut self, bytes: u64) -> bool {
+        if self.exhausted || self.limit.is_some_and(|limit| self.used + bytes > limit) {
+            self.exhausted = true;
+            return false;
+        }
+        self.used += bytes;
+        true
+    }
+
+    /// 上限に収まらず省略したファイルの理由
+    pub fn reason(&self) -> String {
+        let limit = ByteSize(self.limit.unwrap_or_default());
+        format!("skipped: --max-total-bytes {} reached", limit)
+    }
+}
+
+/// 省略・短縮したファイルの一覧を出力フォーマットに合わせて書き込む
+pub fn write_limited<W: Write>(
+    writer: &mut W,
+    limited: &[Limited],
+    output_format: OutputFormat,
+) -> Result<()> {
+    if limited.is_empty() {
+        return Ok(());
+    }
+
+    match output_format {
+        OutputFormat::Plain => {
+            writeln!(writer, "\n\nSkipped or clipped files (size limits):")?;
+            for file in limited {
+                writeln!(writer, "/{} ({})", file.path, file.reason)?;
+            }
+        }
+        OutputFormat::Markdown => {
+            writeln!(writer, "### Skipped or clipped files (size limits)\n")?;
+            for file in limited {
+                writeln!(writer, "- `{}` ({})", file.path, file.reason)?;
+            }
+        }
+        OutputFormat::Xml => {
+            writeln!(writer, "<limited_files>")?;
+            for file in limited {
+                writeln!(
+                    writer,
+                    "<file path=\"{}\" reason=\"{}\" />",
+                    format::escape_xml(&file.path),
+                    format::escape_xml(&file.reason)
+                )?;
+            }
+            writeln!(writer, "</limited_files>")?;
+        }
+        OutputFormat::Json | OutputFormat::Jsonl => {
+            unreachable!("structured formats mark skipped and clipped files in each record")
+        }
+    }
+
+    Ok(())
+}
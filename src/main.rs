@@ -1,71 +1,439 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use std::path::PathBuf;
-
-mod cli;
-mod filefilter;
-mod fileview;
-mod gitutil;
-mod treeview;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use codicat::{charset, cli, config, fileview, format, generated, gitutil, limits, tokens};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Path to render (defaults to current directory)
+    /// Paths or git pathspecs to render, e.g. `src tests ':!*.snap'` (defaults to current directory)
     #[arg(default_value = ".")]
-    path: PathBuf,
+    paths: Vec<PathBuf>,
 
     /// Limit the number of lines printed per file
     #[arg(long, default_value_t = 500)]
     max_lines: usize,
 
+    /// Which lines to keep when a file exceeds --max-lines
+    #[arg(long, value_enum, default_value_t = fileview::Truncate::Head)]
+    truncate: fileview::Truncate,
+
+    /// Skip the contents of files larger than SIZE (accepts K, M and G suffixes)
+    #[arg(long, value_name = "SIZE")]
+    max_file_size: Option<limits::ByteSize>,
+
+    /// Clip lines longer than N characters, marking how many were cut
+    #[arg(long, value_name = "N")]
+    max_line_length: Option<usize>,
+
+    /// Skip further files once the output would exceed SIZE (accepts K, M and G suffixes)
+    #[arg(long, value_name = "SIZE")]
+    max_total_bytes: Option<limits::ByteSize>,
+
+    /// Character encoding of the files, e.g. shift_jis or utf-16le (auto: BOM, working-tree-encoding, then detection)
+    #[arg(long, value_name = "LABEL", default_value_t = charset::Charset::Auto)]
+    encoding: charset::Charset,
+
+    /// How to handle line endings (lf drops stray carriage returns)
+    #[arg(long, value_enum, default_value_t = fileview::Eol::Keep)]
+    eol: fileview::Eol,
+
+    /// Expand tabs to spaces with tab stops every N columns
+    #[arg(long, value_name = "N")]
+    tab_width: Option<usize>,
+
+    /// Remove trailing whitespace from each line
+    #[arg(long, overrides_with = "no_strip_trailing_ws")]
+    strip_trailing_ws: bool,
+
+    /// Keep trailing whitespace even if a config file sets strip-trailing-ws
+    #[arg(long, overrides_with = "strip_trailing_ws", hide = true)]
+    no_strip_trailing_ws: bool,
+
+    /// Collapse runs of blank lines into one, keeping the original line numbers
+    #[arg(long, overrides_with = "no_squeeze_blank")]
+    squeeze_blank: bool,
+
+    /// Keep runs of blank lines even if a config file sets squeeze-blank
+    #[arg(long, overrides_with = "squeeze_blank", hide = true)]
+    no_squeeze_blank: bool,
+
     /// Do not render the tree view
-    #[arg(long)]
+    #[arg(long, overrides_with = "tree")]
     no_tree: bool,
 
+    /// Render the tree view even if a config file sets no-tree
+    #[arg(long, overrides_with = "no_tree", hide = true)]
+    tree: bool,
+
     /// Do not render file contents
-    #[arg(long)]
+    #[arg(long, overrides_with = "content")]
     no_content: bool,
 
+    /// Render file contents even if a config file sets no-content
+    #[arg(long, overrides_with = "no_content", hide = true)]
+    content: bool,
+
     /// Copy output to clipboard
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_copy")]
     copy: bool,
 
+    /// Do not copy output to clipboard even if a config file sets copy
+    #[arg(long, overrides_with = "copy", hide = true)]
+    no_copy: bool,
+
     /// Interactively select files via fzf (if installed)
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_fzf")]
     fzf: bool,
 
+    /// Do not select files via fzf even if a config file sets fzf
+    #[arg(long, overrides_with = "fzf", hide = true)]
+    no_fzf: bool,
+
     /// Filter file paths with a regular expression
     #[arg(long)]
     filter: Option<String>,
 
+    /// Exclude file paths matching a regular expression
+    #[arg(long, value_name = "REGEX")]
+    exclude_regex: Option<String>,
+
+    /// Only include repo-relative paths matching a gitignore-style glob (repeatable)
+    #[arg(long = "include", value_name = "GLOB")]
+    include_globs: Vec<String>,
+
+    /// Exclude repo-relative paths matching a gitignore-style glob (repeatable)
+    #[arg(long = "exclude", value_name = "GLOB")]
+    exclude_globs: Vec<String>,
+
+    /// Do not apply .codicatignore files
+    #[arg(long, overrides_with = "ignore_file")]
+    no_ignore_file: bool,
+
+    /// Apply .codicatignore files even if a config file sets no-ignore-file
+    #[arg(long, overrides_with = "no_ignore_file", hide = true)]
+    ignore_file: bool,
+
     /// Display token count at the end of output
-    #[arg(long)]
+    #[arg(long, overrides_with = "no_token_count")]
     token_count: bool,
 
-    /// Exclude auto-generated files by checking header comments
-    #[arg(long)]
+    /// Do not display the token count even if a config file sets token-count
+    #[arg(long, overrides_with = "token_count", hide = true)]
+    no_token_count: bool,
+
+    /// Exclude auto-generated files (header comments, linguist-generated, -diff, binary)
+    #[arg(long, overrides_with = "no_exclude_generated")]
     exclude_generated: bool,
+
+    /// Keep auto-generated files even if a config file sets exclude-generated
+    #[arg(long, overrides_with = "exclude_generated", hide = true)]
+    no_exclude_generated: bool,
+
+    /// Exclude files marked linguist-vendored in .gitattributes
+    #[arg(long, overrides_with = "no_exclude_vendored")]
+    exclude_vendored: bool,
+
+    /// Keep vendored files even if a config file sets exclude-vendored
+    #[arg(long, overrides_with = "exclude_vendored", hide = true)]
+    no_exclude_vendored: bool,
+
+    /// Exclude files marked linguist-documentation in .gitattributes
+    #[arg(long, overrides_with = "no_exclude_docs")]
+    exclude_docs: bool,
+
+    /// Keep documentation files even if a config file sets exclude-docs
+    #[arg(long, overrides_with = "exclude_docs", hide = true)]
+    no_exclude_docs: bool,
+
+    /// List the excluded files with the rule or option that dropped each one
+    #[arg(long, overrides_with = "no_explain_excluded")]
+    explain_excluded: bool,
+
+    /// Do not list excluded files even if a config file sets explain-excluded
+    #[arg(long, overrides_with = "explain_excluded", hide = true)]
+    no_explain_excluded: bool,
+
+    /// How to render lockfiles (full by default; once set, --exclude-generated keeps them)
+    #[arg(long, value_enum, value_name = "POLICY")]
+    lockfiles: Option<fileview::LockfilePolicy>,
+
+    /// Extra generated-file rules (config file only)
+    #[arg(skip)]
+    generated_rules: Vec<generated::RuleConfig>,
+
+    /// Generated-file rules to disable (config file only)
+    #[arg(skip)]
+    disable_generated_rules: Vec<String>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = format::OutputFormat::Plain)]
+    format: format::OutputFormat,
+
+    /// Pack files in priority order until the token budget is reached
+    #[arg(long, value_name = "N")]
+    max_tokens: Option<usize>,
+
+    /// Glob pattern for files to include first under --max-tokens (repeatable)
+    #[arg(long = "priority", value_name = "GLOB")]
+    priority_globs: Vec<String>,
+
+    /// Annotate each file and directory in the tree view with its token count
+    #[arg(long, overrides_with = "no_tree_tokens")]
+    tree_tokens: bool,
+
+    /// Do not annotate the tree view even if a config file sets tree-tokens
+    #[arg(long, overrides_with = "tree_tokens", hide = true)]
+    no_tree_tokens: bool,
+
+    /// List the N heaviest files and directories by token count
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Encoding used for token counts, budgets and annotations
+    #[arg(long, value_enum, default_value_t = tokens::Encoding::Cl100kBase)]
+    tokenizer: tokens::Encoding,
+
+    /// Only dump files changed since a revision (including uncommitted changes)
+    #[arg(long, value_name = "REV", conflicts_with_all = ["diff", "staged", "unstaged"])]
+    since: Option<String>,
+
+    /// Only dump files changed in a revision range (e.g. main..HEAD), read at the range head
+    #[arg(long, value_name = "BASE..HEAD", conflicts_with_all = ["staged", "unstaged"])]
+    diff: Option<String>,
+
+    /// Only dump files with staged changes
+    #[arg(long)]
+    staged: bool,
+
+    /// Only dump files with unstaged changes
+    #[arg(long)]
+    unstaged: bool,
+
+    /// What to show for changed files in diff mode
+    #[arg(long, value_enum, default_value_t = fileview::DiffContent::Full)]
+    diff_content: fileview::DiffContent,
+
+    /// Render files as of a commit, tag or branch without checking it out
+    #[arg(long, value_name = "TREE-ISH", conflicts_with_all = ["since", "diff", "staged", "unstaged"])]
+    rev: Option<String>,
+
+    /// Also include untracked files that are not ignored
+    #[arg(long, overrides_with = "no_untracked", conflicts_with_all = ["rev", "since", "diff", "staged", "unstaged"])]
+    untracked: bool,
+
+    /// Leave out untracked files even if a config file sets untracked
+    #[arg(long, overrides_with = "untracked", hide = true)]
+    no_untracked: bool,
+
+    /// Show the full tree, marking the files selected for output with `*`
+    #[arg(long, overrides_with = "no_full_tree")]
+    full_tree: bool,
+
+    /// Show only the selected files in the tree even if a config file sets full-tree
+    #[arg(long, overrides_with = "full_tree", hide = true)]
+    no_full_tree: bool,
+
+    /// Number of worker threads for reading and rendering files (0 = number of CPUs)
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    jobs: usize,
+
+    /// How to read the repository (native falls back to the git command on failure)
+    #[arg(long, value_enum, default_value_t = gitutil::BackendKind::default())]
+    git_backend: gitutil::BackendKind,
+
+    /// Apply a named profile from .codicat.toml or ~/.config/codicat/config.toml
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Print the effective settings after applying config files, the profile and flags
+    #[arg(long)]
+    show_config: bool,
+}
+
+/// 設定項目の一覧から、設定ファイルの値の適用と実際の設定の取得を生成する
+/// （`a | b`のbはaを打ち消すフラグで、`;`より後ろは設定ファイルでのみ指定できる項目）
+macro_rules! settings {
+    ($($field:ident $(| $negation:ident)?,)* ; $($config_field:ident,)*) => {
+        impl Args {
+            /// 設定ファイルの値を、コマンドラインで指定されなかった項目に適用する
+            fn apply_settings(&mut self, settings: config::Settings, matches: &ArgMatches) {
+                let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+                $(
+                    let overridden = from_cli(stringify!($field)) $(|| self.$negation)?;
+                    if let (Some(value), false) = (settings.$field, overridden) {
+                        self.$field = value.into();
+                    }
+                )*
+                $(
+                    if let Some(value) = settings.$config_field {
+                        self.$config_field = value;
+                    }
+                )*
+            }
+
+            /// 設定ファイルとコマンドラインを反映した実際の設定を返す
+            fn effective_settings(&self) -> config::Settings {
+                config::Settings {
+                    $($field: self.$field.clone().into(),)*
+                    $($config_field: Some(self.$config_field.clone()),)*
+                }
+            }
+        }
+    };
+}
+
+settings! {
+    max_lines,
+    truncate,
+    max_file_size,
+    max_line_length,
+    max_total_bytes,
+    encoding,
+    eol,
+    tab_width,
+    strip_trailing_ws | no_strip_trailing_ws,
+    squeeze_blank | no_squeeze_blank,
+    no_tree | tree,
+    no_content | content,
+    copy | no_copy,
+    fzf | no_fzf,
+    filter,
+    exclude_regex,
+    include_globs,
+    exclude_globs,
+    no_ignore_file | ignore_file,
+    token_count | no_token_count,
+    exclude_generated | no_exclude_generated,
+    exclude_vendored | no_exclude_vendored,
+    exclude_docs | no_exclude_docs,
+    explain_excluded | no_explain_excluded,
+    lockfiles,
+    format,
+    max_tokens,
+    priority_globs,
+    tree_tokens | no_tree_tokens,
+    top,
+    tokenizer,
+    diff_content,
+    untracked | no_untracked,
+    full_tree | no_full_tree,
+    jobs,
+    ;
+    disable_generated_rules,
+    generated_rules,
+}
+
+impl Args {
+    /// 設定ファイルを探すディレクトリ（最初に指定された存在するパス）
+    fn config_dir(&self) -> &Path {
+        self.paths
+            .iter()
+            .flat_map(|path| path.ancestors())
+            .find(|path| path.is_dir())
+            .unwrap_or_else(|| Path::new("."))
+    }
+
+    /// 差分モードの比較対象を決定する
+    fn diff_spec(&self) -> Option<gitutil::DiffSpec> {
+        match (&self.since, &self.diff, self.staged, self.unstaged) {
+            (Some(rev), _, _, _) => Some(gitutil::DiffSpec::Since(rev.clone())),
+            (_, Some(range), _, _) => Some(gitutil::DiffSpec::Range(range.clone())),
+            // ステージ済みと未ステージの両方はHEADからの変更と同じ
+            (_, _, true, true) => Some(gitutil::DiffSpec::Since("HEAD".to_string())),
+            (_, _, true, false) => Some(gitutil::DiffSpec::Staged),
+            (_, _, false, true) => Some(gitutil::DiffSpec::Unstaged),
+            _ => None,
+        }
+    }
 }
 
 fn main() -> Result<()> {
-    let args = if std::env::args().len() <= 1 {
-        Args::parse_from(vec![std::env::args().next().unwrap(), "--help".to_string()])
+    let matches = if std::env::args().len() <= 1 {
+        Args::command()
+            .get_matches_from(vec![std::env::args().next().unwrap(), "--help".to_string()])
     } else {
-        Args::parse()
+        Args::command().get_matches()
+    };
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    // 設定ファイルの読み込みでもGitを使うため、バックエンドはコマンドラインでのみ指定できる
+    gitutil::set_backend(args.git_backend)?;
+
+    // 設定ファイルの値はコマンドラインで指定されなかった項目にのみ適用する
+    let config = config::Config::load(args.config_dir())?;
+    let settings = config.settings(args.profile.as_deref())?;
+    args.apply_settings(settings, &matches);
+
+    if args.show_config {
+        return print_config(&config, &args);
+    }
+
+    let diff = args.diff_spec();
+    let options = cli::Options {
+        max_lines: args.max_lines,
+        truncate: args.truncate,
+        max_file_size: args.max_file_size.map(|size| size.0),
+        max_line_length: args.max_line_length,
+        max_total_bytes: args.max_total_bytes.map(|size| size.0),
+        encoding: args.encoding,
+        eol: args.eol,
+        tab_width: args.tab_width,
+        strip_trailing_ws: args.strip_trailing_ws,
+        squeeze_blank: args.squeeze_blank,
+        no_tree: args.no_tree,
+        no_content: args.no_content,
+        copy_to_clipboard: args.copy,
+        use_fzf: args.fzf,
+        filter_pattern: args.filter,
+        exclude_regex: args.exclude_regex,
+        include_globs: args.include_globs,
+        exclude_globs: args.exclude_globs,
+        no_ignore_file: args.no_ignore_file,
+        show_token_count: args.token_count,
+        exclude_generated: args.exclude_generated,
+        exclude_vendored: args.exclude_vendored,
+        exclude_docs: args.exclude_docs,
+        generated_rules: args.generated_rules,
+        disabled_generated_rules: args.disable_generated_rules,
+        explain_excluded: args.explain_excluded,
+        lockfiles: args.lockfiles,
+        format: args.format,
+        max_tokens: args.max_tokens,
+        priority_globs: args.priority_globs,
+        tree_tokens: args.tree_tokens,
+        top: args.top,
+        tokenizer: args.tokenizer,
+        diff,
+        diff_content: args.diff_content,
+        rev: args.rev,
+        untracked: args.untracked,
+        full_tree: args.full_tree,
+        jobs: args.jobs,
     };
 
     let app = cli::App::new();
-    app.execute(
-        args.path,
-        args.max_lines,
-        args.no_tree,
-        args.no_content,
-        args.copy,
-        args.fzf,
-        args.filter,
-        args.token_count,
-        args.exclude_generated,
-    )
-    .context("Failed to execute command")
+    app.execute(&args.paths, &options)
+        .context("Failed to execute command")
+}
+
+/// 読み込んだ設定ファイルと実際の設定を表示する
+fn print_config(config: &config::Config, args: &Args) -> Result<()> {
+    let mut paths = config.paths().peekable();
+    if paths.peek().is_none() {
+        println!("# No config files found");
+    }
+    for path in paths {
+        println!("# Loaded: {}", path.display());
+    }
+    if let Some(profile) = &args.profile {
+        println!("# Profile: {}", profile);
+    }
+
+    let settings =
+        toml::to_string(&args.effective_settings()).context("Failed to serialize settings")?;
+    print!("{}", settings);
+
+    Ok(())
 }
@@ -0,0 +1,105 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::Mutex;
+use std::thread;
+
+/// ワーカー1つあたりに先行して処理させる要素数（出力を待つ結果はワーカー数×この値までに抑える）
+const WINDOW_PER_WORKER: usize = 4;
+
+/// ワーカーに渡す処理（要素と結果の送信先）
+type Job<'a, T, R> = (&'a T, SyncSender<R>);
+
+/// 使用するワーカー数を返す（0の場合はCPU数）
+pub fn worker_count(jobs: usize) -> usize {
+    match jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// 要素をワーカーで並列に処理し、結果を元の順序で返すイテレータをbodyに渡す
+/// （先行して処理する要素数に上限があるため、bodyが結果を読むまで処理は進まない）
+pub fn map_ordered<T, R, F, B, O>(items: &[T], jobs: usize, work: F, body: B) -> O
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+    B: FnOnce(&mut dyn Iterator<Item = R>) -> O,
+{
+    let workers = worker_count(jobs).min(items.len());
+    if workers <= 1 {
+        return body(&mut items.iter().map(work));
+    }
+
+    let cancelled = AtomicBool::new(false);
+    let (job_tx, job_rx) = mpsc::channel::<Job<T, R>>();
+    let job_rx = Mutex::new(job_rx);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let (work, job_rx, cancelled) = (&work, &job_rx, &cancelled);
+            scope.spawn(move || {
+                while let Some((item, result_tx)) = next_job(job_rx) {
+                    // 結果が読まれなくなった後の要素は処理しない
+                    if cancelled.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    let _ = result_tx.send(work(item));
+                }
+            });
+        }
+
+        let mut results = Ordered {
+            items: items.iter(),
+            job_tx,
+            pending: VecDeque::new(),
+            window: workers * WINDOW_PER_WORKER,
+            cancelled: &cancelled,
+        };
+        body(&mut results)
+    })
+}
+
+/// 次の処理を受け取る（送信側がすべて閉じられた場合はNone）
+fn next_job<'a, T, R>(job_rx: &Mutex<Receiver<Job<'a, T, R>>>) -> Option<Job<'a, T, R>> {
+    job_rx.lock().ok()?.recv().ok()
+}
+
+/// ワーカーの結果を元の順序で返すイテレータ
+struct Ordered<'a, T, R> {
+    /// まだワーカーに渡していない要素
+    items: std::slice::Iter<'a, T>,
+    /// ワーカーへの処理の送信先（破棄するとワーカーが終了する）
+    job_tx: Sender<Job<'a, T, R>>,
+    /// ワーカーに渡した要素の結果の受信先（要素の順序）
+    pending: VecDeque<Receiver<R>>,
+    /// 同時にワーカーに渡す要素数の上限
+    window: usize,
+    /// 結果が読まれなくなったことをワーカーに伝えるフラグ
+    cancelled: &'a AtomicBool,
+}
+
+impl<T, R> Iterator for Ordered<'_, T, R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        while self.pending.len() < self.window {
+            let Some(item) = self.items.next() else {
+                break;
+            };
+            let (result_tx, result_rx) = mpsc::sync_channel(1);
+            self.job_tx.send((item, result_tx)).ok()?;
+            self.pending.push_back(result_rx);
+        }
+
+        // ワーカーがパニックした場合は受信できないが、パニックはスコープの終了時に伝播する
+        self.pending.pop_front()?.recv().ok()
+    }
+}
+
+impl<T, R> Drop for Ordered<'_, T, R> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
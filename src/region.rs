@@ -0,0 +1,307 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::fmt;
+use std::ops::Range;
+
+/// 宣言の前に付く修飾子（可視性・async・exportなど）
+const MODIFIERS: &str = r#"^\s*(?:(?:pub(?:\([^)]*\))?|const|async|unsafe|extern(?:\s+"[^"]*")?|default|export|static|private|public|protected|abstract|final|override|inline|virtual)\s+)*"#;
+
+/// ファイルの表示範囲（`path:40-120`・`path:120-`・`path#App::execute`）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    /// 行範囲（1始まり、endがNoneの場合はファイルの最後まで）
+    Lines { start: usize, end: Option<usize> },
+    /// 関数・implブロックなどのシンボル（`::`または`.`区切りで外側から指定）
+    Symbol(String),
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Lines {
+                start,
+                end: Some(end),
+            } => write!(f, ":{}-{}", start, end),
+            Region::Lines { start, end: None } => write!(f, ":{}-", start),
+            Region::Symbol(symbol) => write!(f, "#{}", symbol),
+        }
+    }
+}
+
+impl Region {
+    /// 引数をパスと表示範囲に分ける（範囲の書式でない場合はNone）
+    pub fn parse(arg: &str) -> Result<Option<(&str, Region)>> {
+        let symbol = Regex::new(r"^[A-Za-z_]\w*(?:(?:::|\.)[A-Za-z_]\w*)*$")?;
+        if let Some((path, name)) = arg.rsplit_once('#') {
+            if !path.is_empty() && symbol.is_match(name) {
+                return Ok(Some((path, Region::Symbol(name.to_string()))));
+            }
+        }
+
+        let lines = Regex::new(r"^(\d+)(?:(-)(\d*))?$")?;
+        let Some((path, range)) = arg.rsplit_once(':') else {
+            return Ok(None);
+        };
+        let Some(caps) = lines.captures(range).filter(|_| !path.is_empty()) else {
+            return Ok(None);
+        };
+
+        let start = caps[1].parse::<usize>()?;
+        let end = match (caps.get(2), caps.get(3).map(|m| m.as_str())) {
+            // `40`は1行のみ、`40-`はファイルの最後まで
+            (None, _) => Some(start),
+            (Some(_), Some("") | None) => None,
+            (Some(_), Some(end)) => Some(end.parse::<usize>()?),
+        };
+
+        if start == 0 || end.is_some_and(|end| end < start) {
+            anyhow::bail!("Invalid line range: {}", arg);
+        }
+
+        Ok(Some((path, Region::Lines { start, end })))
+    }
+
+    /// ファイルの行から表示する範囲（1始まりの開始行と終了行）を求める
+    pub fn locate(&self, lines: &[String]) -> Result<(usize, usize)> {
+        match self {
+            Region::Lines { start, end } => {
+                if *start > lines.len() {
+                    anyhow::bail!(
+                        "Line {} is past the end of the file ({} lines)",
+                        start,
+                        lines.len()
+                    );
+                }
+                Ok((*start, end.unwrap_or(lines.len()).min(lines.len())))
+            }
+            Region::Symbol(symbol) => {
+                let names = symbol
+                    .split("::")
+                    .flat_map(|s| s.split('.'))
+                    .collect::<Vec<_>>();
+                let found = find_symbol(lines, 0..lines.len(), &names)?
+                    .context(format!("Symbol not found: {}", symbol))?;
+                Ok((found.start + 1, found.end))
+            }
+        }
+    }
+}
+
+/// 宣言の種類（同じ名前の宣言が複数ある場合は小さいものを優先する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Function,
+    Impl,
+    Type,
+    TraitImpl,
+}
+
+/// 名前の宣言にマッチさせる正規表現
+fn declaration_patterns(name: &str) -> Result<Vec<(Kind, Regex)>> {
+    let name = regex::escape(name);
+    let patterns = [
+        (
+            Kind::Function,
+            format!(r"{MODIFIERS}(?:fn|def|func|function)\s+(?:\([^)]*\)\s*)?{name}\b"),
+        ),
+        (
+            Kind::Impl,
+            format!(
+                r"^\s*(?:unsafe\s+)?impl\b(?:\s*<[^{{]*?>)?\s+(?:[\w:]+::)?{name}\b(?:<[^{{]*>)?\s*(?:\{{|where\b|$)"
+            ),
+        ),
+        (
+            Kind::Type,
+            format!(
+                r"{MODIFIERS}(?:struct|enum|trait|mod|union|type|class|interface|object|module|namespace)\s+{name}\b"
+            ),
+        ),
+        (
+            Kind::TraitImpl,
+            format!(r"^\s*(?:unsafe\s+)?impl\b.*\bfor\s+(?:[\w:]+::)?{name}\b"),
+        ),
+    ];
+
+    patterns
+        .into_iter()
+        .map(|(kind, pattern)| Ok((kind, Regex::new(&pattern)?)))
+        .collect()
+}
+
+/// 範囲内から名前の列（外側から順）に当てはまるブロックを探す（0始まりの行範囲）
+fn find_symbol(
+    lines: &[String],
+    scope: Range<usize>,
+    names: &[&str],
+) -> Result<Option<Range<usize>>> {
+    let Some((name, rest)) = names.split_first() else {
+        return Ok(None);
+    };
+
+    let patterns = declaration_patterns(name)?;
+    let mut candidates = Vec::new();
+    for index in scope.clone() {
+        if let Some((kind, _)) = patterns.iter().find(|(_, re)| re.is_match(&lines[index])) {
+            candidates.push((*kind, index));
+        }
+    }
+
+    // 最後の名前は関数・implブロックを優先し、外側の名前は同名のブロックを順に探す
+    if rest.is_empty() {
+        candidates.sort();
+    }
+
+    for (_, index) in candidates {
+        let end = block_end(lines, index).min(scope.end);
+        if rest.is_empty() {
+            return Ok(Some(leading_comments(lines, index, scope.start)..end));
+        }
+        if let Some(found) = find_symbol(lines, index + 1..end, rest)? {
+            return Ok(Some(found));
+        }
+    }
+
+    Ok(None)
+}
+
+/// 宣言の直前にあるドキュメントコメント・属性・デコレータの開始行を返す
+fn leading_comments(lines: &[String], index: usize, lower: usize) -> usize {
+    let mut start = index;
+    while start > lower {
+        let line = lines[start - 1].trim_start();
+        if !(line.starts_with("///") || line.starts_with("#[") || line.starts_with('@')) {
+            break;
+        }
+        start -= 1;
+    }
+    start
+}
+
+/// 字句解析の状態
+enum State {
+    Code,
+    /// 文字列の中（生文字列の場合は`#`の数）
+    Str(Option<usize>),
+    /// ブロックコメントの中（入れ子の深さ）
+    Comment(usize),
+}
+
+/// 宣言から始まるブロックの終わり（次の行の0始まりの番号）を返す
+/// （波括弧の対応で求め、`:`で終わる宣言はインデントで求める）
+fn block_end(lines: &[String], start: usize) -> usize {
+    if lines[start].trim_end().ends_with(':') && !lines[start].contains('{') {
+        return indented_block_end(lines, start);
+    }
+
+    let mut state = State::Code;
+    let mut depth = 0usize;
+    // 波括弧の外側の丸括弧・角括弧の深さ（引数の型の`[u8; 4]`などで終わらないようにする）
+    let mut brackets = 0usize;
+
+    for (index, line) in lines.iter().enumerate().skip(start) {
+        let chars = line.chars().collect::<Vec<_>>();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let next = chars.get(i + 1).copied();
+            match state {
+                State::Code => match chars[i] {
+                    '/' if next == Some('/') => break,
+                    '/' if next == Some('*') => {
+                        state = State::Comment(1);
+                        i += 1;
+                    }
+                    '"' => {
+                        let hashes = raw_string_hashes(&chars, i);
+                        state = State::Str(hashes);
+                    }
+                    '\'' => i += char_literal_len(&chars, i).saturating_sub(1),
+                    '{' => depth += 1,
+                    '}' => {
+                        depth = depth.saturating_sub(1);
+                        if depth == 0 {
+                            return index + 1;
+                        }
+                    }
+                    '(' | '[' if depth == 0 => brackets += 1,
+                    ')' | ']' if depth == 0 => brackets = brackets.saturating_sub(1),
+                    ';' if depth == 0 && brackets == 0 => return index + 1,
+                    _ => {}
+                },
+                State::Str(None) => match chars[i] {
+                    '\\' => i += 1,
+                    '"' => state = State::Code,
+                    _ => {}
+                },
+                State::Str(Some(hashes)) => {
+                    if chars[i] == '"'
+                        && chars[i + 1..].iter().take_while(|&&c| c == '#').count() >= hashes
+                    {
+                        state = State::Code;
+                        i += hashes;
+                    }
+                }
+                State::Comment(nesting) => {
+                    if chars[i] == '*' && next == Some('/') {
+                        state = if nesting == 1 {
+                            State::Code
+                        } else {
+                            State::Comment(nesting - 1)
+                        };
+                        i += 1;
+                    } else if chars[i] == '/' && next == Some('*') {
+                        state = State::Comment(nesting + 1);
+                        i += 1;
+                    }
+                }
+            }
+            i += 1;
+        }
+    }
+
+    lines.len()
+}
+
+/// `"`の位置から始まる文字列が生文字列（`r"`・`r#"`）なら`#`の数を返す
+fn raw_string_hashes(chars: &[char], quote: usize) -> Option<usize> {
+    let hashes = chars[..quote]
+        .iter()
+        .rev()
+        .take_while(|&&c| c == '#')
+        .count();
+    let prefix = quote - hashes;
+    let is_raw = prefix > 0
+        && chars[prefix - 1] == 'r'
+        && (prefix == 1 || !(chars[prefix - 2].is_alphanumeric() || chars[prefix - 2] == '_'));
+    is_raw.then_some(hashes)
+}
+
+/// `'`の位置から始まる文字リテラルの長さを返す（ライフタイムの場合は1）
+fn char_literal_len(chars: &[char], quote: usize) -> usize {
+    match chars.get(quote + 1) {
+        Some('\\') => chars[quote + 2..]
+            .iter()
+            .position(|&c| c == '\'')
+            .map_or(1, |pos| pos + 3),
+        Some(_) if chars.get(quote + 2) == Some(&'\'') => 3,
+        _ => 1,
+    }
+}
+
+/// インデントで表されたブロックの終わり（次の行の0始まりの番号）を返す
+fn indented_block_end(lines: &[String], start: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let base = indent(&lines[start]);
+
+    let mut end = start + 1;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent(line) <= base {
+            break;
+        }
+        end = index + 1;
+    }
+    end
+}
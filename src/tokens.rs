@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tiktoken_rs::{cl100k_base, o200k_base, p50k_base, CoreBPE};
+
+/// 近似計算で1トークンあたりとみなす文字数
+const APPROX_CHARS_PER_TOKEN: usize = 4;
+
+/// トークン数の計算に使うエンコーディング
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// GPT-4o-class models
+    #[value(name = "o200k_base")]
+    O200kBase,
+    /// GPT-4 / GPT-3.5-turbo
+    #[default]
+    #[value(name = "cl100k_base")]
+    Cl100kBase,
+    /// Codex / text-davinci models
+    #[value(name = "p50k_base")]
+    P50kBase,
+    /// Fast approximation (one token per four characters)
+    #[value(name = "approx")]
+    Approx,
+}
+
+impl Encoding {
+    /// エンコーディング名を返す
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::O200kBase => "o200k_base",
+            Encoding::Cl100kBase => "cl100k_base",
+            Encoding::P50kBase => "p50k_base",
+            Encoding::Approx => "approx",
+        }
+    }
+}
+
+/// トークン数を計算する構造体（エンコーディングの読み込みは一度だけ行う）
+pub struct TokenCounter {
+    encoding: Encoding,
+    /// BPEエンコーダ（近似計算の場合はなし）
+    bpe: Option<CoreBPE>,
+}
+
+impl TokenCounter {
+    /// 指定されたエンコーディングでトークンカウンタを作成する
+    pub fn new(encoding: Encoding) -> Result<Self> {
+        let bpe = match encoding {
+            Encoding::O200kBase => {
+                Some(o200k_base().context("Failed to load o200k_base encoding")?)
+            }
+            Encoding::Cl100kBase => {
+                Some(cl100k_base().context("Failed to load cl100k_base encoding")?)
+            }
+            Encoding::P50kBase => Some(p50k_base().context("Failed to load p50k_base encoding")?),
+            Encoding::Approx => None,
+        };
+
+        Ok(Self { encoding, bpe })
+    }
+
+    /// 使用しているエンコーディングを返す
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// テキストのトークン数を計算する
+    pub fn count(&self, text: &str) -> usize {
+        match &self.bpe {
+            Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+            None => text.chars().count().div_ceil(APPROX_CHARS_PER_TOKEN),
+        }
+    }
+}
@@ -1,18 +1,35 @@
 use anyhow::{Context, Result};
-use std::collections::BTreeMap;
+use serde::{Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use crate::gitutil;
 
+/// ルートからの相対パスとトークン数の組
+pub type Weight = (String, usize);
+
 /// ツリーノードを表現する構造体
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TreeNode {
     /// ノード名
     name: String,
     /// ファイルかどうか
     is_file: bool,
+    /// 状態を表す記号（差分モードの変更種別など）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    marker: Option<String>,
+    /// トークン数（ディレクトリは配下の合計）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tokens: Option<usize>,
+    /// 強調表示するかどうか（全体のツリーで出力対象のファイル）
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    highlighted: bool,
     /// 子ノード
+    #[serde(
+        skip_serializing_if = "BTreeMap::is_empty",
+        serialize_with = "serialize_children"
+    )]
     children: BTreeMap<String, TreeNode>,
 }
 
@@ -22,20 +39,133 @@ impl TreeNode {
         Self {
             name: name.to_string(),
             is_file,
+            marker: None,
+            tokens: None,
+            highlighted: false,
             children: BTreeMap::new(),
         }
     }
+
+    /// 各ファイルのトークン数を計算し、ディレクトリには配下の合計を設定する
+    pub fn annotate_tokens<F: FnMut(&Path) -> usize>(
+        &mut self,
+        path: &Path,
+        count: &mut F,
+    ) -> usize {
+        let tokens = if self.is_file {
+            count(path)
+        } else {
+            let mut total = 0;
+            for child in self.children.values_mut() {
+                let child_path = path.join(&child.name);
+                total += child.annotate_tokens(&child_path, count);
+            }
+            total
+        };
+
+        self.tokens = Some(tokens);
+        tokens
+    }
+
+    /// 条件を満たすファイルを強調表示する
+    pub fn highlight<F: FnMut(&Path) -> bool>(&mut self, path: &Path, is_highlighted: &mut F) {
+        if self.is_file {
+            self.highlighted = is_highlighted(path);
+            return;
+        }
+
+        for child in self.children.values_mut() {
+            let child_path = path.join(&child.name);
+            child.highlight(&child_path, is_highlighted);
+        }
+    }
+
+    /// トークン数の多いファイルとディレクトリをそれぞれ上位n件返す
+    pub fn heaviest(&self, n: usize) -> (Vec<Weight>, Vec<Weight>) {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        collect_weights(self, "", &mut files, &mut dirs);
+
+        for weights in [&mut files, &mut dirs] {
+            weights.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            weights.truncate(n);
+        }
+
+        (files, dirs)
+    }
+
+    /// 表示用のラベルを返す
+    fn label(&self, show_tokens: bool) -> String {
+        let mut label = match &self.marker {
+            Some(marker) => format!("[{}] {}", marker, self.name),
+            None => self.name.clone(),
+        };
+
+        if self.highlighted {
+            label.push_str(" *");
+        }
+
+        if let (Some(tokens), true) = (self.tokens, show_tokens) {
+            label.push_str(&format!(" ({} tokens)", tokens));
+        }
+
+        label
+    }
+}
+
+/// ファイルとディレクトリのトークン数をルートからの相対パスとともに集める
+fn collect_weights(node: &TreeNode, prefix: &str, files: &mut Vec<Weight>, dirs: &mut Vec<Weight>) {
+    for child in node.children.values() {
+        let path = if prefix.is_empty() {
+            child.name.clone()
+        } else {
+            format!("{}/{}", prefix, child.name)
+        };
+        let tokens = child.tokens.unwrap_or(0);
+
+        if child.is_file {
+            files.push((path, tokens));
+        } else {
+            dirs.push((format!("{}/", path), tokens));
+            collect_weights(child, &path, files, dirs);
+        }
+    }
 }
 
 /// 指定されたパスからGit管理下のファイルのツリービューを構築して表示する
 pub fn tree_view_from_git<P: AsRef<Path>, W: Write>(input_path: P, writer: &mut W) -> Result<()> {
     let tree = build_tree_from_git(input_path)?;
-    print_tree_root(&tree, writer)?;
+    print_tree(&tree, writer, false)?;
     Ok(())
 }
 
+/// 子ノードを名前順の配列としてシリアライズする
+fn serialize_children<S: Serializer>(
+    children: &BTreeMap<String, TreeNode>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(children.values())
+}
+
 /// Git管理下のファイルからツリー構造を構築する
-fn build_tree_from_git<P: AsRef<Path>>(input_path: P) -> Result<TreeNode> {
+pub fn build_tree_from_git<P: AsRef<Path>>(input_path: P) -> Result<TreeNode> {
+    let abs_input = input_path
+        .as_ref()
+        .canonicalize()
+        .context("Failed to resolve input path")?;
+
+    let git_root = gitutil::get_git_root(&abs_input)?;
+    let git_files = gitutil::list_git_tracked_files(&git_root)?;
+
+    build_tree_from_files(&abs_input, &git_files, &HashMap::new())
+}
+
+/// ファイル一覧から入力パス配下のツリー構造を構築する（markersに含まれるファイルには記号を付ける）
+pub fn build_tree_from_files<P: AsRef<Path>>(
+    input_path: P,
+    files: &[PathBuf],
+    markers: &HashMap<PathBuf, String>,
+) -> Result<TreeNode> {
     let abs_input = input_path
         .as_ref()
         .canonicalize()
@@ -48,9 +178,7 @@ fn build_tree_from_git<P: AsRef<Path>>(input_path: P) -> Result<TreeNode> {
         .unwrap_or_else(|_| Path::new("."))
         .to_path_buf();
 
-    let git_files = gitutil::list_git_tracked_files(&git_root)?;
-
-    let relevant_paths = git_files
+    let relevant_paths = files
         .iter()
         .filter_map(|file| {
             let rel_path = file.strip_prefix(&git_root).ok()?;
@@ -62,7 +190,7 @@ fn build_tree_from_git<P: AsRef<Path>>(input_path: P) -> Result<TreeNode> {
             {
                 // 相対パスの先頭部分（rel_input_path）を除去
                 let trimmed = rel_path.strip_prefix(&rel_input_path).unwrap_or(rel_path);
-                Some(trimmed.to_path_buf())
+                Some((trimmed.to_path_buf(), markers.get(file)))
             } else {
                 None
             }
@@ -80,22 +208,24 @@ fn build_tree_from_git<P: AsRef<Path>>(input_path: P) -> Result<TreeNode> {
 
     let mut root = TreeNode::new(&root_name, false);
 
-    for path in relevant_paths {
-        insert_path(&mut root, &path);
+    for (path, marker) in relevant_paths {
+        if let Some(leaf) = insert_path(&mut root, &path) {
+            leaf.marker = marker.cloned();
+        }
     }
 
     Ok(root)
 }
 
-/// パスをツリー構造に挿入する
-fn insert_path(node: &mut TreeNode, path: &Path) {
+/// パスをツリー構造に挿入し、末端のノードを返す
+fn insert_path<'a>(node: &'a mut TreeNode, path: &Path) -> Option<&'a mut TreeNode> {
     let components: Vec<_> = path
         .components()
         .map(|c| c.as_os_str().to_string_lossy().to_string())
         .collect();
 
     if components.is_empty() {
-        return;
+        return None;
     }
 
     let mut current = node;
@@ -115,17 +245,24 @@ fn insert_path(node: &mut TreeNode, path: &Path) {
 
         current = current.children.get_mut(component).unwrap();
     }
+
+    Some(current)
 }
 
-/// ツリーのルートノードを表示する
-fn print_tree_root<W: Write>(node: &TreeNode, writer: &mut W) -> Result<()> {
-    writeln!(writer, "{}", node.name)?;
-    print_tree_children(node, "", writer)?;
+/// ツリーをルートノードから表示する（show_tokensが有効ならトークン数を併記する）
+pub fn print_tree<W: Write>(node: &TreeNode, writer: &mut W, show_tokens: bool) -> Result<()> {
+    writeln!(writer, "{}", node.label(show_tokens))?;
+    print_tree_children(node, "", writer, show_tokens)?;
     Ok(())
 }
 
 /// ツリーの子ノードを再帰的に表示する
-fn print_tree_children<W: Write>(node: &TreeNode, prefix: &str, writer: &mut W) -> Result<()> {
+fn print_tree_children<W: Write>(
+    node: &TreeNode,
+    prefix: &str,
+    writer: &mut W,
+    show_tokens: bool,
+) -> Result<()> {
     for (i, (_, child)) in node.children.iter().enumerate() {
         let is_last_child = i == node.children.len() - 1;
 
@@ -140,10 +277,16 @@ fn print_tree_children<W: Write>(node: &TreeNode, prefix: &str, writer: &mut W)
             format!("{}│ ", prefix)
         };
 
-        writeln!(writer, "{}{} {}", prefix, connector, child.name)?;
+        writeln!(
+            writer,
+            "{}{} {}",
+            prefix,
+            connector,
+            child.label(show_tokens)
+        )?;
 
         if !child.is_file {
-            print_tree_children(child, &next_prefix, writer)?;
+            print_tree_children(child, &next_prefix, writer, show_tokens)?;
         }
     }
 
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::path::PathBuf;
+
+use codicat::budget::{self, Candidate, Priority};
+
+// テスト用の候補を作成する
+fn candidate(rel_path: &str, tokens: usize) -> Candidate<String> {
+    Candidate {
+        path: PathBuf::from(rel_path),
+        rel_path: rel_path.to_string(),
+        tokens,
+        item: rel_path.to_string(),
+    }
+}
+
+#[test]
+fn test_pack_within_budget_keeps_original_order() -> Result<()> {
+    let priority = Priority::new::<PathBuf>(None, &[], &[])?;
+    let candidates = vec![
+        candidate("src/large.rs", 80),
+        candidate("src/small.rs", 10),
+        candidate("src/medium.rs", 30),
+    ];
+
+    let packing = budget::pack(candidates, 50, &priority);
+
+    // トークン数の少ない順に予算内まで選ばれ、出力は元の順序を保つことを確認
+    assert_eq!(packing.selected, vec!["src/small.rs", "src/medium.rs"]);
+    assert_eq!(packing.omitted.len(), 1);
+    assert_eq!(packing.omitted[0].path, "src/large.rs");
+    assert_eq!(packing.omitted[0].tokens, 80);
+
+    Ok(())
+}
+
+#[test]
+fn test_pack_priority_order() -> Result<()> {
+    let priority = Priority::new::<PathBuf>(None, &["src/*.rs".to_string()], &[])?;
+    let candidates = vec![
+        candidate("README.md", 30),
+        candidate("docs/guide.md", 10),
+        candidate("src/main.rs", 40),
+        candidate("src/nested/mod.rs", 5),
+    ];
+
+    let packing = budget::pack(candidates, 75, &priority);
+
+    // globに一致するファイル、READMEの順に優先され、残りは省略されることを確認
+    assert_eq!(
+        packing.selected,
+        vec!["README.md", "src/main.rs", "src/nested/mod.rs"]
+    );
+    let omitted = packing
+        .omitted
+        .iter()
+        .map(|o| o.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(omitted, vec!["docs/guide.md"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_priority_glob() {
+    let result = Priority::new::<PathBuf>(None, &["src/[".to_string()], &[]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_priority_relative_to_root() -> Result<()> {
+    let root = PathBuf::from("/repo");
+    let priority = Priority::new::<PathBuf>(Some(&root), &["src/main.rs".to_string()], &[])?;
+
+    // 表示用のパスがサブディレクトリからの相対パスでも、Gitルートからの相対パスにマッチさせることを確認
+    let candidates = vec![
+        Candidate {
+            path: root.join("src/lib.rs"),
+            rel_path: "lib.rs".to_string(),
+            tokens: 10,
+            item: "src/lib.rs",
+        },
+        Candidate {
+            path: root.join("src/main.rs"),
+            rel_path: "main.rs".to_string(),
+            tokens: 20,
+            item: "src/main.rs",
+        },
+    ];
+
+    let packing = budget::pack(candidates, 20, &priority);
+    assert_eq!(packing.selected, vec!["src/main.rs"]);
+
+    Ok(())
+}
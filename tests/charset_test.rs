@@ -0,0 +1,67 @@
+use anyhow::Result;
+use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+
+use codicat::charset::{self, Charset};
+
+/// 判定に使う日本語のソースコード
+const SOURCE: &str = "// 日本語のコメントです。これはテストです。\nfn main() {\n    println!(\"こんにちは、世界\");\n}\n";
+
+/// UTF-16の文字列をバイト列に変換する
+fn utf16(text: &str, little_endian: bool) -> Vec<u8> {
+    text.encode_utf16()
+        .flat_map(|unit| match little_endian {
+            true => unit.to_le_bytes(),
+            false => unit.to_be_bytes(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_detect_encoding() {
+    // BOMがある場合はBOMに従うことを確認
+    let mut with_bom = vec![0xFF, 0xFE];
+    with_bom.extend(utf16(SOURCE, true));
+    assert_eq!(charset::detect(&with_bom), UTF_16LE);
+
+    // BOMのないUTF-16はASCII文字のNULの位置から判定されることを確認
+    assert_eq!(charset::detect(&utf16("hello world\n", true)), UTF_16LE);
+    assert_eq!(charset::detect(&utf16("hello world\n", false)), UTF_16BE);
+
+    // 日本語のレガシーな文字コードは内容から推測されることを確認
+    assert_eq!(charset::detect(&SHIFT_JIS.encode(SOURCE).0), SHIFT_JIS);
+    assert_eq!(charset::detect(&EUC_JP.encode(SOURCE).0), EUC_JP);
+
+    // UTF-8とバイナリはUTF-8のままであることを確認
+    assert_eq!(charset::detect(SOURCE.as_bytes()), UTF_8);
+    assert_eq!(charset::detect(&[0x00, 0x01, 0x02, 0xFF].repeat(10)), UTF_8);
+}
+
+#[test]
+fn test_decode_is_lossy() {
+    // 変換できないバイトはエラーにならず置換文字になることを確認
+    assert_eq!(charset::decode(b"ok \xFF\n", UTF_8), "ok \u{FFFD}\n");
+
+    // BOMは取り除かれることを確認
+    assert_eq!(charset::decode(b"\xEF\xBB\xBFtext", UTF_8), "text");
+    assert_eq!(
+        charset::decode(&SHIFT_JIS.encode(SOURCE).0, SHIFT_JIS),
+        SOURCE
+    );
+}
+
+#[test]
+fn test_parse_charset() -> Result<()> {
+    assert_eq!("auto".parse::<Charset>()?, Charset::Auto);
+    assert_eq!("sjis".parse::<Charset>()?, Charset::Fixed(SHIFT_JIS));
+    assert_eq!("UTF-16LE".parse::<Charset>()?, Charset::Fixed(UTF_16LE));
+    assert!("klingon".parse::<Charset>().is_err());
+
+    // 設定ファイルに書き出した値を読み戻せることを確認
+    assert_eq!(Charset::Fixed(SHIFT_JIS).to_string(), "shift_jis");
+    assert_eq!(
+        Charset::Fixed(EUC_JP).to_string().parse::<Charset>()?,
+        Charset::Fixed(EUC_JP)
+    );
+
+    Ok(())
+}
@@ -356,6 +356,1137 @@ fn test_japanese_filename_handling() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_markdown_format_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    let (stdout, _) = run_codicat_with_args(&["--format", "markdown"], Some(repo.path()))?;
+
+    // ツリーとファイルがMarkdownのコードブロックとして出力されているか確認
+    assert!(stdout.starts_with("```\n"));
+    assert!(stdout.contains("### a.txt\n\n```\nline 1\n"));
+    assert!(stdout.contains("### sub/c.txt"));
+    assert!(!stdout.contains("1 | line 1"));
+    assert!(!stdout.contains(&"-".repeat(80)));
+
+    Ok(())
+}
+
+#[test]
+fn test_xml_format_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    create_binary_file(repo.path())?;
+
+    let (stdout, _) =
+        run_codicat_with_args(&["--format", "xml", "--max-lines", "2"], Some(repo.path()))?;
+
+    // ツリーとファイルがXML要素で囲まれているか確認
+    assert!(stdout.starts_with("<directory_structure>\n"));
+    assert!(stdout.contains("</directory_structure>\n<files>\n"));
+    assert!(stdout.contains("<file path=\"a.txt\" lines=\"5\" truncated=\"true\" omitted=\"3\">"));
+    assert!(stdout.contains("line 2\n... [3 lines omitted] ...\n]]>"));
+    assert!(stdout.contains("<file path=\"sub/c.txt\""));
+    assert!(stdout.contains("<file path=\"binary.bin\" binary=\"true\" />"));
+    assert!(stdout.ends_with("</files>\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_json_format_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    create_binary_file(repo.path())?;
+
+    let (stdout, _) =
+        run_codicat_with_args(&["--format", "json", "--max-lines", "2"], Some(repo.path()))?;
+    let document: serde_json::Value = serde_json::from_str(&stdout)?;
+
+    // ツリーが再帰的にシリアライズされているか確認
+    let children = document["tree"]["children"].as_array().unwrap();
+    assert!(children.iter().any(|c| c["name"] == "sub"
+        && c["children"][0]["name"] == "c.txt"
+        && c["children"][0]["is_file"] == true));
+
+    // ファイルごとのメタデータと内容が含まれているか確認
+    let files = document["files"].as_array().unwrap();
+    let a_txt = files.iter().find(|f| f["path"] == "a.txt").unwrap();
+    assert_eq!(a_txt["lines"], 5);
+    assert_eq!(a_txt["truncated"], true);
+    assert_eq!(a_txt["binary"], false);
+    assert_eq!(
+        a_txt["content"],
+        "line 1\nline 2\n... [3 lines omitted] ...\n"
+    );
+    assert_eq!(a_txt["omitted_lines"], serde_json::json!([[3, 5]]));
+    assert!(a_txt["tokens"].as_u64().unwrap() > 0);
+
+    let binary = files.iter().find(|f| f["path"] == "binary.bin").unwrap();
+    assert_eq!(binary["binary"], true);
+    assert!(binary.get("content").is_none());
+
+    // 集計が含まれているか確認
+    assert_eq!(document["summary"]["files"], 4);
+    assert_eq!(document["summary"]["binary_files"], 1);
+    assert_eq!(document["summary"]["truncated_files"], 3);
+
+    // トークン数は標準エラー出力に表示され、標準出力はJSONとして解析できることを確認
+    for format in ["json", "jsonl"] {
+        let (stdout, stderr) =
+            run_codicat_with_args(&["--format", format, "--token-count"], Some(repo.path()))?;
+        if format == "json" {
+            serde_json::from_str::<serde_json::Value>(&stdout)?;
+        } else {
+            for line in stdout.lines() {
+                serde_json::from_str::<serde_json::Value>(line)?;
+            }
+        }
+        assert!(!stdout.contains("Token count: "));
+        assert!(stderr.contains("Token count: "));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_jsonl_format_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    let (stdout, _) = run_codicat_with_args(&["--format", "jsonl"], Some(repo.path()))?;
+    let records = stdout
+        .lines()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // ツリー、ファイルごとのレコード、集計の順に1行ずつ出力されているか確認
+    assert_eq!(records.len(), 5);
+    assert_eq!(records[0]["type"], "tree");
+    assert_eq!(records[1]["type"], "file");
+    assert_eq!(records[1]["path"], "a.txt");
+    assert_eq!(records[3]["path"], "sub/c.txt");
+    assert_eq!(records[4]["type"], "summary");
+    assert_eq!(records[4]["files"], 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_max_tokens_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // 大きなファイルを追加
+    let large_content = (1..=500)
+        .map(|i| format!("line {}\n", i))
+        .collect::<String>();
+    fs::write(repo.path().join("large.txt"), large_content)?;
+    Command::new("git")
+        .args(["add", "large.txt"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to git add large file")?;
+
+    let (stdout, _) = run_codicat_with_args(
+        &["--no-tree", "--max-tokens", "200", "--priority", "b.txt"],
+        Some(repo.path()),
+    )?;
+
+    // 予算内のファイルは表示され、収まらないファイルは省略一覧に表示されるか確認
+    assert!(stdout.contains("/a.txt\n"));
+    assert!(stdout.contains("/b.txt\n"));
+    assert!(!stdout.contains("line 500"));
+    assert!(stdout.contains("Omitted files (exceeded --max-tokens):"));
+    assert!(stdout.contains("/large.txt ("));
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_tokens_and_top_options() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    let (stdout, _) = run_codicat_with_args(
+        &["--tree-tokens", "--top", "2", "--no-content"],
+        Some(repo.path()),
+    )?;
+
+    // ツリーの各行にトークン数が表示されているか確認
+    assert!(stdout.contains("├── a.txt ("));
+    assert!(stdout.contains("└── sub ("));
+    assert!(stdout.contains("  └── c.txt ("));
+
+    // 上位のファイルとディレクトリが表示されているか確認
+    assert!(stdout.contains("Top 2 files by tokens:"));
+    assert!(stdout.contains("Top 2 directories by tokens:"));
+    assert!(stdout.contains("  sub/\n"));
+
+    // JSONでは上位の一覧が標準エラー出力に表示され、標準出力はJSONとして解析できることを確認
+    let (stdout, stderr) =
+        run_codicat_with_args(&["--format", "json", "--top", "2"], Some(repo.path()))?;
+    serde_json::from_str::<serde_json::Value>(&stdout)?;
+    assert!(!stdout.contains("Top 2 files by tokens:"));
+    assert!(stderr.contains("Top 2 files by tokens:"));
+
+    Ok(())
+}
+
+#[test]
+fn test_tokenizer_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    let (default_stdout, _) = run_codicat_with_args(&["--token-count"], Some(repo.path()))?;
+    let (stdout, _) = run_codicat_with_args(
+        &["--token-count", "--tokenizer", "o200k_base"],
+        Some(repo.path()),
+    )?;
+
+    // 選択したエンコーディングがトークン数とともに表示されるか確認
+    assert!(default_stdout.contains("(cl100k_base)"));
+    assert!(stdout.contains("Token count: "));
+    assert!(stdout.contains("(o200k_base)"));
+
+    // JSONの集計にもエンコーディングが含まれるか確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--format", "json", "--tokenizer", "approx"],
+        Some(repo.path()),
+    )?;
+    let document: serde_json::Value = serde_json::from_str(&stdout)?;
+    assert_eq!(document["summary"]["tokenizer"], "approx");
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_mode_options() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // a.txtを変更し、b.txtを削除する
+    fs::write(repo.path().join("a.txt"), "line 1\nchanged line\n")?;
+    Command::new("git")
+        .args(["rm", "-q", "b.txt"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to git rm file")?;
+
+    let (stdout, _) = run_codicat_with_args(&["--since", "HEAD"], Some(repo.path()))?;
+
+    // ツリーには変更されたパスだけが状態記号付きで表示されるか確認
+    assert!(stdout.contains("├── [M] a.txt"));
+    assert!(stdout.contains("└── [D] b.txt"));
+    assert!(!stdout.contains("c.txt"));
+
+    // 変更されたファイルの内容と、削除されたファイルの差分が表示されるか確認
+    assert!(stdout.contains("2 | changed line"));
+    assert!(stdout.contains("/b.txt [D]"));
+    assert!(stdout.contains("-line 5"));
+
+    // 差分のヘッダーはファイルのヘッダーと同じ形式で前のファイルのフッターに続くか確認
+    let rule = "-".repeat(80);
+    assert!(stdout.contains(&format!("\n\n{}\n/b.txt [D]\n{}\n", rule, rule)));
+
+    let (stdout, _) = run_codicat_with_args(
+        &["--unstaged", "--diff-content", "hunks"],
+        Some(repo.path()),
+    )?;
+
+    // 未ステージの変更のみが差分として表示されるか確認
+    assert!(stdout.contains("/a.txt [M]"));
+    assert!(stdout.contains("+changed line"));
+    assert!(!stdout.contains("2 | changed line"));
+    assert!(!stdout.contains("b.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_range_reads_head_revision() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // 2つ目のコミットでファイルを追加・変更し、その後作業ツリーから削除・変更する
+    fs::write(repo.path().join("sub/new.txt"), "committed line\n")?;
+    fs::write(repo.path().join("a.txt"), "line 1\ncommitted change\n")?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+    Command::new("git")
+        .args(["commit", "-qm", "Second commit"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to commit")?;
+    fs::remove_file(repo.path().join("sub/new.txt"))?;
+    fs::write(repo.path().join("a.txt"), "worktree change\n")?;
+
+    // 作業ツリーにないファイルも含め、範囲の変更後のリビジョンから内容が読み込まれるか確認
+    let (stdout, stderr) = run_codicat_with_args(&["--diff", "HEAD~1..HEAD"], Some(repo.path()))?;
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert!(stdout.contains("├── [M] a.txt"));
+    assert!(stdout.contains("1 | committed line"));
+    assert!(stdout.contains("2 | committed change"));
+    assert!(!stdout.contains("worktree change"));
+
+    // 変更後を省略した範囲はHEADまでの変更として扱われるか確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--diff", "HEAD~1..", "--format", "json"],
+        Some(repo.path()),
+    )?;
+    let document: serde_json::Value = serde_json::from_str(&stdout)?;
+    let new_txt = document["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["path"] == "sub/new.txt")
+        .unwrap();
+    assert_eq!(new_txt["content"], "committed line\n");
+
+    Ok(())
+}
+
+#[test]
+fn test_staged_diff_reads_index() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // ステージした後に作業ツリーのファイルを変更・削除する
+    fs::write(
+        repo.path().join("a.txt"),
+        "line 1
+staged change
+",
+    )?;
+    fs::write(
+        repo.path().join("sub/new.txt"),
+        "staged line
+",
+    )?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+    fs::write(
+        repo.path().join("a.txt"),
+        "line 1
+worktree change
+",
+    )?;
+    fs::remove_file(repo.path().join("sub/new.txt"))?;
+
+    // 作業ツリーではなく、インデックスにステージされた内容が表示されるか確認
+    let (stdout, stderr) = run_codicat_with_args(&["--staged"], Some(repo.path()))?;
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert!(stdout.contains("├── [M] a.txt"));
+    assert!(stdout.contains("2 | staged change"));
+    assert!(!stdout.contains("worktree change"));
+    assert!(stdout.contains("/sub/new.txt\n"));
+    assert!(stdout.contains("1 | staged line"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // 2つ目のコミットでa.txtを変更し、b.txtを削除する
+    fs::write(repo.path().join("a.txt"), "line 1\nchanged line\n")?;
+    Command::new("git")
+        .args(["rm", "-q", "b.txt"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to git rm file")?;
+    Command::new("git")
+        .args(["commit", "-qam", "Second commit"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to commit")?;
+
+    let (stdout, _) = run_codicat_with_args(&["--rev", "HEAD~1"], Some(repo.path()))?;
+
+    // 1つ前のコミットのツリーと内容が表示されるか確認
+    assert!(stdout.contains("├── b.txt"));
+    assert!(stdout.contains("2 | line 2"));
+    assert!(!stdout.contains("changed line"));
+
+    // 作業ツリーが変更されていないことを確認
+    assert!(!repo.path().join("b.txt").exists());
+
+    let (_, stderr) = run_codicat_with_args(&["--rev", "nope"], Some(repo.path()))?;
+    assert!(stderr.contains("Unknown revision: nope"));
+
+    Ok(())
+}
+
+#[test]
+fn test_rev_reads_filter_files_from_revision() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // コミットした.gitattributes・.codicatignoreと異なる内容に作業ツリーを変更する
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "a.txt linguist-generated\n",
+    )?;
+    fs::write(repo.path().join(".codicatignore"), "b.txt\n")?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+    Command::new("git")
+        .args(["commit", "-qm", "Add filter files"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to commit")?;
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "sub/c.txt linguist-generated\n",
+    )?;
+    fs::write(repo.path().join(".codicatignore"), "")?;
+
+    // 作業ツリーではなく、リビジョンに含まれる設定で除外されるか確認
+    let (stdout, stderr) = run_codicat_with_args(
+        &[
+            "--rev",
+            "HEAD",
+            "--exclude-generated",
+            "--no-tree",
+            "--explain-excluded",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert!(!stdout.contains("/a.txt\n"));
+    assert!(!stdout.contains("/b.txt\n"));
+    assert!(stdout.contains("/sub/c.txt\n"));
+    assert!(stdout.contains("/a.txt  (gitattributes: linguist-generated)"));
+
+    Ok(())
+}
+
+#[test]
+fn test_untracked_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    fs::write(repo.path().join("sub/new.txt"), "untracked line\n")?;
+
+    // 指定しない場合は未追跡ファイルが含まれないことを確認
+    let (stdout, _) = run_codicat_with_args(&["."], Some(repo.path()))?;
+    assert!(!stdout.contains("new.txt"));
+
+    let (stdout, _) = run_codicat_with_args(&["--untracked"], Some(repo.path()))?;
+
+    // ツリーに記号付きで表示され、内容も出力されるか確認
+    assert!(stdout.contains("[?] new.txt"));
+    assert!(stdout.contains("/sub/new.txt"));
+    assert!(stdout.contains("1 | untracked line"));
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_follows_selection() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // ツリーにもフィルタ後のファイルのみが表示されるか確認
+    let (stdout, _) = run_codicat_with_args(&["--filter", "a\\.txt"], Some(repo.path()))?;
+    assert!(stdout.contains("└── a.txt"));
+    assert!(!stdout.contains("b.txt"));
+    assert!(!stdout.contains("sub"));
+
+    // --full-treeでは全体のツリーを表示し、出力対象のファイルを強調表示するか確認
+    let (stdout, _) =
+        run_codicat_with_args(&["--filter", "a\\.txt", "--full-tree"], Some(repo.path()))?;
+    assert!(stdout.contains("├── a.txt *"));
+    assert!(stdout.contains("├── b.txt\n"));
+    assert!(stdout.contains("c.txt\n"));
+    assert!(!stdout.contains("/b.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_multiple_paths_and_pathspecs() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::write(repo.path().join("sub/d.snap"), "snapshot\n")?;
+    Command::new("git")
+        .args(["add", "sub/d.snap"])
+        .current_dir(repo.path())
+        .output()
+        .context("Failed to git add file")?;
+
+    // サブディレクトリを指定した場合は、その配下の内容のみが出力されるか確認
+    let (stdout, _) = run_codicat_with_args(&["sub"], Some(repo.path()))?;
+    assert!(stdout.contains("/sub/c.txt"));
+    assert!(!stdout.contains("/a.txt"));
+
+    // 複数のパスと除外のpathspecを指定し、重複するパスは1度だけ出力されるか確認
+    let (stdout, _) = run_codicat_with_args(
+        &["sub", "a.txt", "sub/c.txt", ":!*.snap"],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("├── a.txt"));
+    assert!(stdout.contains("└── c.txt"));
+    assert!(!stdout.contains("b.txt"));
+    assert!(!stdout.contains("d.snap"));
+    assert_eq!(stdout.matches("/sub/c.txt").count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_include_exclude_options() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // リポジトリからの相対パスに対してglobパターンでマッチさせる
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--include",
+            "*.txt",
+            "--exclude",
+            "sub/",
+            "--exclude",
+            "b.txt",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("/a.txt"));
+    assert!(!stdout.contains("b.txt"));
+    assert!(!stdout.contains("c.txt"));
+
+    // 正規表現で除外する
+    let (stdout, _) =
+        run_codicat_with_args(&["--exclude-regex", "[ab]\\.txt$"], Some(repo.path()))?;
+    assert!(!stdout.contains("/a.txt"));
+    assert!(!stdout.contains("/b.txt"));
+    assert!(stdout.contains("/sub/c.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_ignore_file() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // ルートとサブディレクトリに.codicatignoreを置く（サブディレクトリの設定が優先される）
+    fs::write(repo.path().join(".codicatignore"), "*.txt\n")?;
+    fs::write(repo.path().join("sub/.codicatignore"), "!c.txt\n")?;
+
+    // 除外されたファイルはツリーと内容のどちらにも表示されないことを確認
+    let (stdout, _) = run_codicat_with_args(&["."], Some(repo.path()))?;
+    assert!(!stdout.contains("a.txt"));
+    assert!(!stdout.contains("b.txt"));
+    assert!(stdout.contains("└── c.txt"));
+    assert!(stdout.contains("/sub/c.txt"));
+
+    // --no-ignore-fileで.codicatignoreを無視する
+    let (stdout, _) = run_codicat_with_args(&["--no-ignore-file", "."], Some(repo.path()))?;
+    assert!(stdout.contains("/a.txt"));
+    assert!(stdout.contains("/b.txt"));
+    assert!(stdout.contains("/sub/c.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_exclude_by_gitattributes() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "a.txt linguist-generated\nb.txt linguist-documentation\nsub/** linguist-vendored\n",
+    )?;
+
+    // 属性が指定されていてもオプションがなければ除外されないことを確認
+    let (stdout, _) = run_codicat_with_args(&["."], Some(repo.path()))?;
+    assert!(stdout.contains("/a.txt"));
+    assert!(stdout.contains("/b.txt"));
+    assert!(stdout.contains("/sub/c.txt"));
+
+    // 各オプションで対応する属性のファイルのみが除外されることを確認
+    let (stdout, _) = run_codicat_with_args(&["--exclude-generated", "."], Some(repo.path()))?;
+    assert!(!stdout.contains("/a.txt"));
+    assert!(stdout.contains("/b.txt"));
+
+    let (stdout, _) = run_codicat_with_args(&["--exclude-docs", "."], Some(repo.path()))?;
+    assert!(stdout.contains("/a.txt"));
+    assert!(!stdout.contains("/b.txt"));
+
+    let (stdout, _) = run_codicat_with_args(&["--exclude-vendored", "."], Some(repo.path()))?;
+    assert!(stdout.contains("/a.txt"));
+    assert!(!stdout.contains("/sub/c.txt"));
+
+    Ok(())
+}
+
+#[test]
+fn test_explain_excluded() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    create_generated_file(repo.path())?;
+    fs::write(
+        repo.path().join(".codicat.toml"),
+        "[[generated-rule]]\nname = \"fixtures\"\npaths = [\"sub/\"]\n",
+    )?;
+
+    // 除外したファイルとマッチしたルールやオプションが表示されることを確認
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--exclude-generated",
+            "--exclude-regex",
+            "b\\.txt$",
+            "--explain-excluded",
+            ".",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("Excluded files: 3"));
+    assert!(stdout.contains("/b.txt  (--exclude-regex)"));
+    assert!(stdout.contains("/generated.rs  (generated: generated-marker)"));
+    assert!(stdout.contains("/sub/c.txt  (generated: fixtures)"));
+    assert!(stdout.contains("/a.txt\n"));
+
+    // JSON Linesでは除外の一覧が標準エラー出力に表示され、各行がJSONとして解析できることを確認
+    let (stdout, stderr) = run_codicat_with_args(
+        &[
+            "--format",
+            "jsonl",
+            "--exclude-generated",
+            "--explain-excluded",
+        ],
+        Some(repo.path()),
+    )?;
+    for line in stdout.lines() {
+        serde_json::from_str::<serde_json::Value>(line)?;
+    }
+    assert!(!stdout.contains("Excluded files:"));
+    assert!(stderr.contains("Excluded files: 2"));
+
+    Ok(())
+}
+
+#[test]
+fn test_lockfiles_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::write(
+        repo.path().join("Cargo.lock"),
+        "version = 4\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.219\"\nsource = \"registry\"\n",
+    )?;
+    Command::new("git")
+        .args(["add", "Cargo.lock"])
+        .current_dir(repo.path())
+        .output()?;
+
+    // summaryでは内容が「package@version」の一覧に置き換えられることを確認
+    let (stdout, _) = run_codicat_with_args(&["--lockfiles", "summary", "."], Some(repo.path()))?;
+    assert!(stdout.contains("serde@1.0.219"));
+    assert!(!stdout.contains("source = "));
+
+    // omitではツリーと内容の両方から除外されることを確認
+    let (stdout, _) = run_codicat_with_args(&["--lockfiles", "omit", "."], Some(repo.path()))?;
+    assert!(!stdout.contains("Cargo.lock"));
+    assert!(stdout.contains("/a.txt"));
+
+    // 指定した場合は--exclude-generatedでもロックファイルを除外しないことを確認
+    let (stdout, _) = run_codicat_with_args(&["--exclude-generated", "."], Some(repo.path()))?;
+    assert!(!stdout.contains("Cargo.lock"));
+    let (stdout, _) = run_codicat_with_args(
+        &["--exclude-generated", "--lockfiles", "full", "."],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("source = "));
+
+    Ok(())
+}
+
+#[test]
+fn test_streaming_output() -> Result<()> {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::write(repo.path().join("big.txt"), "line\n".repeat(200_000))?;
+    Command::new("git")
+        .args(["add", "big.txt"])
+        .current_dir(repo.path())
+        .output()?;
+
+    // 最初の行を読んだところで出力先を閉じても、エラーにならずに終了することを確認
+    let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin("codicat"))
+        .arg(".")
+        .current_dir(repo.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut first_line = String::new();
+    BufReader::new(child.stdout.take().context("stdout is not piped")?)
+        .read_line(&mut first_line)?;
+    let output = child.wait_with_output()?;
+
+    assert!(!first_line.is_empty());
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr)?.is_empty());
+
+    // トークン数を表示する場合も出力全体が標準出力に書き込まれることを確認
+    let (stdout, _) = run_codicat_with_args(&["--token-count", "a.txt"], Some(repo.path()))?;
+    assert!(stdout.starts_with("/a.txt\n"));
+    assert!(stdout.contains("Token count: "));
+
+    // JSON Linesでもファイルごとのレコードと集計が出力されることを確認
+    let (stdout, _) = run_codicat_with_args(&["--format", "jsonl", "sub"], Some(repo.path()))?;
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains("sub/c.txt"));
+    assert!(lines[2].contains("\"summary\""));
+
+    Ok(())
+}
+
+#[test]
+fn test_parallel_rendering_is_deterministic() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    for i in 0..50 {
+        fs::write(
+            repo.path().join(format!("sub/f{:02}.txt", i)),
+            "x\n".repeat(i * 10),
+        )?;
+    }
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+
+    // ワーカー数によらず出力が同じになることを確認
+    for format in ["plain", "jsonl"] {
+        let (sequential, _) =
+            run_codicat_with_args(&["--jobs", "1", "--format", format, "."], Some(repo.path()))?;
+        let (parallel, _) =
+            run_codicat_with_args(&["--jobs", "8", "--format", format, "."], Some(repo.path()))?;
+        assert!(sequential.contains("f49.txt"));
+        assert_eq!(sequential, parallel);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_region_specs() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // 行範囲を指定したファイルは範囲の行のみが表示されることを確認
+    let (stdout, _) =
+        run_codicat_with_args(&["--no-tree", "a.txt:2-3", "b.txt"], Some(repo.path()))?;
+    assert!(stdout.contains("/a.txt:2-3\n"));
+    assert!(stdout.contains("   2 | line 2\n   3 | line 3\n"));
+    let a_section = stdout.split("/b.txt").next().unwrap_or_default();
+    assert!(!a_section.contains("line 1"));
+    assert!(!a_section.contains("line 4"));
+    assert!(stdout.contains("/b.txt\n"));
+
+    // 範囲付きのファイルもツリーに含まれることを確認
+    let (stdout, _) = run_codicat_with_args(&["a.txt:4-", "sub"], Some(repo.path()))?;
+    assert!(stdout.contains("a.txt"));
+    assert!(stdout.contains("c.txt"));
+    assert!(stdout.contains("   5 | line 5"));
+
+    // ディレクトリに範囲は指定できないことを確認
+    let (_, stderr) = run_codicat_with_args(&["sub:1-2"], Some(repo.path()))?;
+    assert!(stderr.contains("can only be used with files"));
+
+    Ok(())
+}
+
+#[test]
+fn test_truncate_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // 末尾を残す場合は省略箇所のマーカーの後に元の行番号で表示されることを確認
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--no-tree",
+            "--max-lines",
+            "2",
+            "--truncate",
+            "tail",
+            "a.txt",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("... [3 lines omitted] ...\n   4 | line 4\n   5 | line 5\n"));
+
+    // JSONでは省略した行の範囲が記録されることを確認
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--format",
+            "json",
+            "--max-lines",
+            "2",
+            "--truncate",
+            "head-tail",
+            "a.txt",
+        ],
+        Some(repo.path()),
+    )?;
+    let json: serde_json::Value = serde_json::from_str(&stdout)?;
+    let file = &json["files"][0];
+    assert_eq!(file["truncated"], true);
+    assert_eq!(file["omitted_lines"], serde_json::json!([[2, 4]]));
+    assert_eq!(
+        file["content"],
+        "line 1\n... [3 lines omitted] ...\nline 5\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_size_limit_options() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::write(repo.path().join("dump.sql"), "INSERT;\n".repeat(1000))?;
+    fs::write(
+        repo.path().join("app.min.js"),
+        format!("{}\n", "x".repeat(500)),
+    )?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+
+    // 大きいファイルの内容を省略し、長い行を短縮して、最後に一覧を表示することを確認
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--no-tree",
+            "--max-file-size",
+            "4K",
+            "--max-line-length",
+            "100",
+            ".",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("[file skipped: 7.8 KiB exceeds --max-file-size]"));
+    assert!(!stdout.contains("INSERT;"));
+    let clipped = format!("   1 | {} ... [400 chars clipped]\n", "x".repeat(100));
+    assert!(stdout.contains(&clipped));
+    assert!(stdout.ends_with(
+        "Skipped or clipped files (size limits):\n\
+         /app.min.js (clipped 1 line to --max-line-length 100)\n\
+         /dump.sql (skipped: 7.8 KiB exceeds --max-file-size 4K)\n"
+    ));
+
+    // 出力全体の上限を超えたファイル以降は、収まる大きさのファイルも省略して一覧に表示することを確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--no-tree", "--max-total-bytes", "800", "."],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("/a.txt\n---"));
+    assert!(!stdout.contains("/app.min.js\n---"));
+    assert!(!stdout.contains("/b.txt\n---"));
+    assert!(stdout.ends_with(
+        "Skipped or clipped files (size limits):\n\
+         /app.min.js (skipped: --max-total-bytes 800 reached)\n\
+         /b.txt (skipped: --max-total-bytes 800 reached)\n\
+         /dump.sql (skipped: --max-total-bytes 800 reached)\n\
+         /sub/c.txt (skipped: --max-total-bytes 800 reached)\n"
+    ));
+
+    // JSONでは各レコードと集計に記録されることを確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--format", "json", "--max-file-size", "4K", "."],
+        Some(repo.path()),
+    )?;
+    let json: serde_json::Value = serde_json::from_str(&stdout)?;
+    let dump = json["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|f| f["path"] == "dump.sql")
+        .unwrap();
+    assert_eq!(dump["skipped"], true);
+    assert!(dump.get("content").is_none());
+    assert_eq!(json["summary"]["skipped_files"], 1);
+
+    Ok(())
+}
+
+#[test]
+fn test_encoding_option() -> Result<()> {
+    let repo = setup_git_repo()?;
+    fs::write(
+        repo.path().join("legacy.txt"),
+        encoding_rs::SHIFT_JIS
+            .encode("これは日本語の文章を表示するテストです。\n")
+            .0,
+    )?;
+    fs::write(
+        repo.path().join("tagged.txt"),
+        encoding_rs::EUC_JP.encode("表").0,
+    )?;
+    fs::write(
+        repo.path().join(".gitattributes"),
+        "tagged.txt working-tree-encoding=EUC-JP\n",
+    )?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+
+    // 推測した文字コードと`working-tree-encoding`の指定に従って変換されることを確認
+    let (stdout, _) = run_codicat_with_args(&["--no-tree", "."], Some(repo.path()))?;
+    assert!(stdout.contains("/legacy.txt\n"));
+    assert!(stdout.contains("   1 | これは日本語の文章を表示するテストです。\n"));
+    assert!(stdout.contains("   1 | 表\n"));
+
+    // `--encoding`の指定は推測や属性より優先され、変換できないバイトでも失敗しないことを確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--no-tree", "--encoding", "utf-8", "."],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("   1 | \u{FFFD}"));
+    assert!(!stdout.contains("日本語"));
+
+    Ok(())
+}
+
+#[test]
+fn test_normalization_options() -> Result<()> {
+    let repo = setup_git_repo()?;
+    fs::write(
+        repo.path().join("spaced.txt"),
+        "first   \n\n\n\n\n\tsecond\t\n",
+    )?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+
+    // 正規化した内容と、オプションごとに削減したトークン数が表示されることを確認
+    let (stdout, _) = run_codicat_with_args(
+        &[
+            "--no-tree",
+            "--strip-trailing-ws",
+            "--squeeze-blank",
+            "--tab-width",
+            "2",
+            "--token-count",
+            "spaced.txt",
+        ],
+        Some(repo.path()),
+    )?;
+    assert!(stdout.contains("   1 | first\n   2 | \n   6 |   second\n"));
+    assert!(stdout.contains("Saved by --tab-width 2: "));
+    assert!(stdout.contains("Saved by --strip-trailing-ws: "));
+
+    let saved = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Saved by --squeeze-blank: "))
+        .context("squeeze-blank savings not reported")?;
+    assert!(saved.trim_end_matches(" tokens").parse::<i64>()? > 0);
+
+    // 正規化を指定しない場合は削減量を表示しないことを確認
+    let (stdout, _) = run_codicat_with_args(
+        &["--no-tree", "--token-count", "spaced.txt"],
+        Some(repo.path()),
+    )?;
+    assert!(!stdout.contains("Saved by"));
+
+    // タブの展開でトークン数が増えた場合は、負の削減量ではなく増加量として表示することを確認
+    fs::write(
+        repo.path().join("tabs.txt"),
+        "a\tb\tc\td\te\tf\tg\n\t\t\t\t\t\tx\n",
+    )?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+    let (stdout, _) = run_codicat_with_args(
+        &["--no-tree", "--tab-width", "4", "--token-count", "tabs.txt"],
+        Some(repo.path()),
+    )?;
+    let added = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Added by --tab-width 4: "))
+        .context("tab-width increase not reported")?;
+    assert!(added.trim_end_matches(" tokens").parse::<i64>()? > 0);
+    assert!(!stdout.contains(": -"));
+
+    Ok(())
+}
+
+#[test]
+fn test_paths_relative_to_git_root() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    let sub_dir = repo.path().join("sub");
+
+    // サブディレクトリから実行しても、パスはGitルートからの相対パスで出力されるか確認
+    let (stdout, _) = run_codicat_with_args(&["--format", "json"], Some(&sub_dir))?;
+    let document: serde_json::Value = serde_json::from_str(&stdout)?;
+    let paths = document["files"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["path"].as_str().unwrap_or_default())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["sub/c.txt"]);
+
+    let (stdout, _) = run_codicat_with_args(&["--format", "xml"], Some(&sub_dir))?;
+    assert!(stdout.contains("<file path=\"sub/c.txt\""));
+
+    let (stdout, _) = run_codicat_with_args(&["--no-tree"], Some(&sub_dir))?;
+    assert!(stdout.starts_with("/sub/c.txt\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_paths_outside_current_dir() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+    fs::create_dir_all(repo.path().join("other"))?;
+    fs::write(repo.path().join("other/d.txt"), "other\n")?;
+    Command::new("git")
+        .args(["add", "other/d.txt"])
+        .current_dir(repo.path())
+        .output()?;
+    let sub_dir = repo.path().join("sub");
+    let host_path = repo.path().to_string_lossy().to_string();
+
+    // カレントディレクトリの外を指定しても、パスはGitルートからの相対パスで出力されるか確認
+    let (stdout, _) = run_codicat_with_args(&["--no-tree", "../other"], Some(&sub_dir))?;
+    assert!(stdout.starts_with("/other/d.txt\n"));
+    assert!(!stdout.contains(&host_path));
+
+    let (stdout, _) = run_codicat_with_args(&["--format", "xml", "../other"], Some(&sub_dir))?;
+    assert!(stdout.contains("<file path=\"other/d.txt\""));
+    assert!(!stdout.contains(&host_path));
+
+    // Gitリポジトリ外のファイルでもホストの絶対パスを出力しないことを確認
+    let outside = TempDir::new()?;
+    fs::write(outside.path().join("note.txt"), "note\n")?;
+    let note = outside.path().join("note.txt");
+    let (stdout, _) = run_codicat_with_args(&[note.to_str().unwrap()], Some(&sub_dir))?;
+    assert!(stdout.contains("/note.txt\n"));
+    assert!(!stdout.contains(&outside.path().to_string_lossy().to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn test_app_execute_does_not_reuse_previous_run() -> Result<()> {
+    let changed = setup_git_repo()?;
+    create_test_files(changed.path())?;
+    fs::write(changed.path().join("a.txt"), "changed\n")?;
+    let clean = setup_git_repo()?;
+    create_test_files(clean.path())?;
+
+    let app = codicat::cli::App::new();
+    let options = codicat::cli::Options {
+        no_tree: true,
+        diff: Some(codicat::gitutil::DiffSpec::Unstaged),
+        ..Default::default()
+    };
+
+    // 同じAppで続けて実行しても、前回の変更一覧を使わずに対象を取得し直すか確認
+    app.execute(&[changed.path()], &options)?;
+    let err = app
+        .execute(&[clean.path()], &options)
+        .expect_err("stale changes were reused");
+    assert!(
+        err.to_string().contains("No changed files found"),
+        "{}",
+        err
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_config_file_and_profiles() -> Result<()> {
+    let repo = setup_git_repo()?;
+    create_test_files(repo.path())?;
+
+    // ユーザー設定とプロジェクト設定を作成する
+    let config_home = TempDir::new()?;
+    fs::create_dir_all(config_home.path().join("codicat"))?;
+    fs::write(
+        config_home.path().join("codicat/config.toml"),
+        "max-lines = 1\nno-tree = true\n",
+    )?;
+    fs::write(
+        repo.path().join(".codicat.toml"),
+        "max-lines = 2\n\n[profile.review]\ninclude = [\"sub/\"]\nformat = \"markdown\"\n",
+    )?;
+
+    let run = |args: &[&str]| -> Result<String> {
+        let output = Command::cargo_bin("codicat")?
+            .current_dir(repo.path())
+            .env("XDG_CONFIG_HOME", config_home.path())
+            .args(args)
+            .output()?;
+        Ok(String::from_utf8(output.stdout)?)
+    };
+
+    // プロジェクト設定がユーザー設定より優先され、指定のない項目はユーザー設定が使われるか確認
+    let stdout = run(&["."])?;
+    assert!(stdout.contains("2 | line 2"));
+    assert!(!stdout.contains("3 | line 3"));
+    assert!(!stdout.contains("└──"));
+
+    // プロファイルの設定が適用され、コマンドラインの指定が最も優先されるか確認
+    let stdout = run(&["--profile", "review", "--max-lines", "3", "."])?;
+    assert!(stdout.contains("### sub/c.txt"));
+    assert!(stdout.contains("line 3\n"));
+    assert!(!stdout.contains("line 4"));
+    assert!(!stdout.contains("a.txt"));
+
+    // 実際の設定と読み込んだファイルが表示されるか確認
+    let stdout = run(&["--profile", "review", "--show-config"])?;
+    assert!(stdout.contains("# Loaded: "));
+    assert!(stdout.contains("# Profile: review"));
+    assert!(stdout.contains("max-lines = 2"));
+    assert!(stdout.contains("no-tree = true"));
+    assert!(stdout.contains("format = \"markdown\""));
+
+    // 設定ファイルで有効にした項目をコマンドラインで無効にできるか確認
+    let stdout = run(&["--tree", "."])?;
+    assert!(stdout.contains("└──"));
+    let stdout = run(&["--tree", "--show-config"])?;
+    assert!(stdout.contains("no-tree = false"));
+    let stdout = run(&["--tree", "--no-tree", "."])?;
+    assert!(!stdout.contains("└──"));
+
+    // configという名前のディレクトリもパスとして扱われるか確認
+    fs::create_dir_all(repo.path().join("config"))?;
+    fs::write(repo.path().join("config/e.txt"), "config line\n")?;
+    Command::new("git")
+        .args(["add", "config/e.txt"])
+        .current_dir(repo.path())
+        .output()?;
+    let stdout = run(&["config"])?;
+    assert!(stdout.contains("/config/e.txt"));
+    assert!(stdout.contains("config line"));
+
+    Ok(())
+}
+
 // ゴールデンファイルを生成するテスト（通常はignore）
 #[test]
 #[ignore]
@@ -0,0 +1,81 @@
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+use codicat::filefilter::{GlobFilter, IgnoreFiles, IGNORE_FILE};
+
+// パターンを文字列のベクタに変換する
+fn patterns(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn test_glob_filter_include() -> Result<()> {
+    let root = Path::new("/repo");
+    let filter = GlobFilter::new(root, &patterns(&["src/**/*.rs", "!src/gen/**"]), &[])?;
+
+    // `**`で任意の深さにマッチし、`!`で打ち消したパターンは含めないことを確認
+    assert!(filter.is_match(&root.join("src/main.rs")));
+    assert!(filter.is_match(&root.join("src/parser/lexer.rs")));
+    assert!(!filter.is_match(&root.join("src/gen/bindings.rs")));
+    assert!(!filter.is_match(&root.join("tests/main.rs")));
+
+    // リポジトリ外のパスはマッチしないことを確認
+    assert!(!filter.is_match(Path::new("/other/src/main.rs")));
+
+    Ok(())
+}
+
+#[test]
+fn test_glob_filter_exclude() -> Result<()> {
+    let root = Path::new("/repo");
+    let filter = GlobFilter::new(root, &[], &patterns(&["*.snap", "fixtures/", "/docs"]))?;
+
+    // `/`を含まないパターンは任意の階層のファイル名にマッチすることを確認
+    assert!(!filter.is_match(&root.join("tests/parser/out.snap")));
+
+    // 末尾が`/`のパターンはディレクトリ配下のファイルを除外することを確認
+    assert!(!filter.is_match(&root.join("tests/fixtures/input.txt")));
+    assert!(filter.is_match(&root.join("tests/fixtures.rs")));
+
+    // 先頭が`/`のパターンはルート直下のみにマッチすることを確認
+    assert!(!filter.is_match(&root.join("docs/index.md")));
+    assert!(filter.is_match(&root.join("src/docs/index.md")));
+
+    Ok(())
+}
+
+#[test]
+fn test_ignore_files_nested() -> Result<()> {
+    let temp = TempDir::new()?;
+    let root = temp.path();
+    fs::create_dir_all(root.join("src/gen"))?;
+    fs::write(root.join(IGNORE_FILE), "*.log\ngen/\n")?;
+    fs::write(root.join("src").join(IGNORE_FILE), "!keep.log\n")?;
+
+    let files = [
+        "a.rs",
+        "a.log",
+        "src/keep.log",
+        "src/other.log",
+        "src/gen/x.rs",
+    ]
+    .iter()
+    .map(|p| root.join(p))
+    .collect::<Vec<_>>();
+    let ignore = IgnoreFiles::load(root, &files, None)?;
+
+    // 親ディレクトリのパターンは配下のファイルにも適用されることを確認
+    assert!(ignore.is_ignored(&root.join("a.log")));
+    assert!(ignore.is_ignored(&root.join("src/other.log")));
+    assert!(ignore.is_ignored(&root.join("src/gen/x.rs")));
+
+    // 深い階層の`!`パターンが親ディレクトリのパターンを打ち消すことを確認
+    assert!(!ignore.is_ignored(&root.join("src/keep.log")));
+
+    let kept = ignore.filter(files);
+    assert_eq!(kept, vec![root.join("a.rs"), root.join("src/keep.log")]);
+
+    Ok(())
+}
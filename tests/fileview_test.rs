@@ -3,7 +3,9 @@ use std::fs::File;
 use std::io::Write;
 use tempfile::TempDir;
 
-use codicat::fileview;
+use codicat::fileview::{self, Eol, LockfilePolicy, Normalize, Truncate, ViewOptions};
+use codicat::format::OutputFormat;
+use codicat::region::Region;
 
 #[test]
 fn test_render_file_with_line_limit() -> Result<()> {
@@ -109,3 +111,437 @@ fn test_file_footer_format() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_markdown_code_block() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let rust_file = tmp_dir.path().join("sample.rs");
+
+    std::fs::write(&rust_file, "fn main() {}\n")?;
+
+    let mut buf = Vec::new();
+    fileview::file_view(
+        &rust_file,
+        &mut buf,
+        &ViewOptions::new(0, OutputFormat::Markdown),
+    )?;
+
+    let output = String::from_utf8(buf)?;
+
+    // 見出しと言語タグ付きのコードブロックで表示されていることを確認
+    assert!(output.contains("### "));
+    assert!(output.contains("sample.rs"));
+    assert!(output.contains("```rust\nfn main() {}\n```\n"));
+    assert!(!output.contains("1 | "));
+
+    Ok(())
+}
+
+#[test]
+fn test_markdown_fence_longer_than_content_backticks() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let md_file = tmp_dir.path().join("README.md");
+
+    std::fs::write(&md_file, "```sh\necho hi\n```\n")?;
+
+    let mut buf = Vec::new();
+    fileview::file_view(
+        &md_file,
+        &mut buf,
+        &ViewOptions::new(0, OutputFormat::Markdown),
+    )?;
+
+    let output = String::from_utf8(buf)?;
+
+    // ファイル内のバッククォートより長いフェンスが使われていることを確認
+    assert!(output.contains("````markdown\n```sh\n"));
+    assert!(output.contains("```\n````\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_xml_file_element() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let text_file = tmp_dir.path().join("sample.txt");
+
+    std::fs::write(&text_file, "line 1\nline 2 ]]> end\nline 3\n")?;
+
+    let mut buf = Vec::new();
+    fileview::file_view(
+        &text_file,
+        &mut buf,
+        &ViewOptions::new(2, OutputFormat::Xml),
+    )?;
+
+    let output = String::from_utf8(buf)?;
+
+    // 行数と切り詰めの有無が属性として出力されていることを確認
+    assert!(output.contains("lines=\"3\" truncated=\"true\" omitted=\"1\">"));
+    // CDATAの終端文字列がセクションの分割で表現されていることを確認
+    assert!(output
+        .contains("<![CDATA[line 1\nline 2 ]]]]><![CDATA[> end\n... [1 line omitted] ...\n]]>"));
+    assert!(!output.contains("line 3"));
+    assert!(output.ends_with("</file>\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_lockfile_summary() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let options = ViewOptions {
+        lockfiles: LockfilePolicy::Summary,
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+
+    let lockfiles = [
+        (
+            "Cargo.lock",
+            "version = 4\n\n[[package]]\nname = \"serde\"\nversion = \"1.0.219\"\n\n[[package]]\nname = \"anyhow\"\nversion = \"1.0.97\"\n",
+        ),
+        (
+            "yarn.lock",
+            "# yarn lockfile v1\n\n\"@babel/core@^7.0.0\", \"@babel/core@^7.1.0\":\n  version \"7.24.0\"\n\nanyhow@^1:\n  version \"1.0.97\"\n",
+        ),
+        (
+            "pnpm-lock.yaml",
+            "lockfileVersion: '9.0'\n\npackages:\n  '@babel/core@7.24.0':\n    resolution: {}\n  anyhow@1.0.97(peer@1.0.0):\n    resolution: {}\n",
+        ),
+        (
+            "package-lock.json",
+            r#"{"packages":{"":{"name":"app"},"node_modules/anyhow":{"version":"1.0.97"},"node_modules/x/node_modules/@babel/core":{"version":"7.24.0"}}}"#,
+        ),
+    ];
+
+    // 形式ごとに「package@version」の一覧に置き換えられることを確認
+    for (name, content) in lockfiles {
+        let path = tmp_dir.path().join(name);
+        std::fs::write(&path, content)?;
+
+        let file = fileview::load_file(&path, &options)?;
+        assert_eq!(file.lines[0], "# 2 packages (lockfile summary)", "{}", name);
+        assert!(file.lines[1..].iter().all(|l| l.contains('@')), "{}", name);
+        assert!(file
+            .lines
+            .iter()
+            .any(|l| l == "anyhow@1.0.97" || l == "serde@1.0.219"));
+    }
+
+    // 解析できない場合は元の内容を表示することを確認
+    let path = tmp_dir.path().join("go.sum");
+    std::fs::write(&path, "")?;
+    let file = fileview::load_file(&path, &options)?;
+    assert_eq!(file.lines, vec!["# 0 packages (lockfile summary)"]);
+
+    let path = tmp_dir.path().join("Pipfile.lock");
+    std::fs::write(&path, "not json")?;
+    let file = fileview::load_file(&path, &options)?;
+    assert_eq!(file.lines, vec!["not json"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_lockfile_summary_non_ascii_names() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let options = ViewOptions {
+        lockfiles: LockfilePolicy::Summary,
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+
+    let lockfiles = [
+        ("yarn.lock", "\"ünicode@^1.0.0\":\n  version \"1.0.0\"\n"),
+        (
+            "pnpm-lock.yaml",
+            "packages:\n  ü@1.0.0:\n    resolution: {}\n",
+        ),
+    ];
+
+    // 名前の先頭がマルチバイト文字でも、パニックせずに名前とバージョンを取り出せることを確認
+    for (name, content) in lockfiles {
+        let path = tmp_dir.path().join(name);
+        std::fs::write(&path, content)?;
+
+        let file = fileview::load_file(&path, &options)?;
+        assert_eq!(file.lines[0], "# 1 package (lockfile summary)", "{}", name);
+        assert!(
+            file.lines[1] == "ünicode@1.0.0" || file.lines[1] == "ü@1.0.0",
+            "{}",
+            name
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_render_region() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let path = tmp_dir.path().join("lib.rs");
+    std::fs::write(
+        &path,
+        "use std::io;\n\nfn main() {\n    run();\n}\n\nfn run() {}\n",
+    )?;
+
+    // 行範囲は元の行番号で表示され、ヘッダーに範囲が付くことを確認
+    let options = ViewOptions {
+        region: Some(Region::Lines {
+            start: 3,
+            end: Some(4),
+        }),
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+    let mut output = Vec::new();
+    fileview::file_view(&path, &mut output, &options)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("lib.rs:3-4\n"));
+    assert!(output.contains("   3 | fn main() {\n   4 |     run();\n"));
+    assert!(!output.contains("   5 | "));
+
+    // シンボルを指定した場合はその関数のみが読み込まれることを確認
+    let options = ViewOptions {
+        region: Some(Region::Symbol("main".to_string())),
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+    let file = fileview::load_file(&path, &options)?;
+    assert_eq!(file.range, Some((3, 5)));
+    assert_eq!(file.lines, vec!["fn main() {", "    run();", "}"]);
+    assert_eq!(file.total_lines, 7);
+    assert!(!file.is_truncated());
+
+    Ok(())
+}
+
+#[test]
+fn test_truncate_strategies() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let path = tmp_dir.path().join("ten.txt");
+    let content = (1..=10)
+        .map(|i| format!("line {}\n", i))
+        .collect::<String>();
+    std::fs::write(&path, content)?;
+
+    let render = |truncate: Truncate| -> Result<String> {
+        let options = ViewOptions {
+            truncate,
+            ..ViewOptions::new(4, OutputFormat::Plain)
+        };
+        let mut output = Vec::new();
+        fileview::file_view(&path, &mut output, &options)?;
+        Ok(String::from_utf8(output)?)
+    };
+
+    // 省略箇所にマーカーが入り、元の行番号が保たれることを確認
+    let head = render(Truncate::Head)?;
+    assert!(head.contains("   4 | line 4\n... [6 lines omitted] ...\n"));
+
+    let tail = render(Truncate::Tail)?;
+    assert!(tail.contains("... [6 lines omitted] ...\n   7 | line 7\n"));
+    assert!(tail.contains("  10 | line 10\n"));
+
+    let head_tail = render(Truncate::HeadTail)?;
+    assert!(head_tail.contains("   2 | line 2\n... [6 lines omitted] ...\n   9 | line 9\n"));
+
+    let middle = render(Truncate::Middle)?;
+    assert!(middle.contains("... [3 lines omitted] ...\n   4 | line 4\n"));
+    assert!(middle.contains("   7 | line 7\n... [3 lines omitted] ...\n"));
+
+    // 省略した範囲が記録されることを確認
+    let options = ViewOptions {
+        truncate: Truncate::Middle,
+        ..ViewOptions::new(4, OutputFormat::Markdown)
+    };
+    let file = fileview::load_file(&path, &options)?;
+    assert!(file.is_truncated());
+    assert_eq!(file.omitted, vec![(1, 3), (8, 10)]);
+    assert_eq!(file.lines, vec!["line 4", "line 5", "line 6", "line 7"]);
+    assert!(file
+        .text()
+        .starts_with("... [3 lines omitted] ...\nline 4\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_size_limits() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let bundle = tmp_dir.path().join("bundle.min.js");
+    std::fs::write(&bundle, format!("{}\nshort\n", "x".repeat(30)))?;
+
+    // 長い行は先頭だけを残し、短縮した文字数をマーカーで示すことを確認
+    let options = ViewOptions {
+        max_line_length: Some(10),
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+    let mut output = Vec::new();
+    let file = fileview::file_view(&bundle, &mut output, &options)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("   1 | xxxxxxxxxx ... [20 chars clipped]\n   2 | short\n"));
+    assert_eq!(file.clipped_lines, 1);
+    assert_eq!(
+        file.limited(&options).map(|l| l.reason),
+        Some("clipped 1 line to --max-line-length 10".to_string())
+    );
+
+    // 上限を超えるファイルは内容を読まずにサイズだけを示すことを確認
+    let options = ViewOptions {
+        max_file_size: Some(16),
+        ..ViewOptions::new(0, OutputFormat::Xml)
+    };
+    let mut output = Vec::new();
+    let file = fileview::file_view(&bundle, &mut output, &options)?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("size=\"37\" skipped=\"true\" />"));
+    assert!(!output.contains("short"));
+    assert!(file.skipped && file.lines.is_empty());
+    assert_eq!(
+        file.limited(&options).map(|l| l.reason),
+        Some("skipped: 37 B exceeds --max-file-size 16".to_string())
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_render_legacy_encodings() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let sjis = tmp_dir.path().join("sjis.txt");
+    std::fs::write(&sjis, encoding_rs::SHIFT_JIS.encode("こんにちは\n世界\n").0)?;
+
+    // Shift_JISのファイルがUTF-8に変換されて表示されることを確認
+    let mut output = Vec::new();
+    let file = fileview::file_view(&sjis, &mut output, &ViewOptions::new(0, OutputFormat::Xml))?;
+    let output = String::from_utf8(output)?;
+    assert!(output.contains("encoding=\"Shift_JIS\" lines=\"2\""));
+    assert!(output.contains("こんにちは\n世界\n"));
+    assert_eq!(file.encoding, Some("Shift_JIS"));
+
+    // NULを含むUTF-16のファイルがバイナリとして扱われないことを確認
+    let utf16 = tmp_dir.path().join("utf16.txt");
+    let mut bytes = vec![0xFF, 0xFE];
+    bytes.extend(
+        "line 1\r\nline 2\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    std::fs::write(&utf16, bytes)?;
+    let file = fileview::load_file(&utf16, &ViewOptions::new(0, OutputFormat::Plain))?;
+    assert!(!file.is_binary);
+    assert_eq!(file.lines, vec!["line 1", "line 2"]);
+
+    // 指定した文字コードで変換できないバイトは置換文字になり、失敗しないことを確認
+    let options = ViewOptions {
+        encoding: "utf-8".parse()?,
+        ..ViewOptions::new(0, OutputFormat::Plain)
+    };
+    let file = fileview::load_file(&sjis, &options)?;
+    assert!(file.lines[0].contains('\u{FFFD}'));
+    assert_eq!(file.encoding, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_head_lines_read_line_by_line() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+
+    // 先頭の行だけを表示する場合も、全体の行数と省略した範囲が記録されることを確認
+    let long = tmp_dir.path().join("long.txt");
+    let content = (1..=10000)
+        .map(|i| format!("line {}\n", i))
+        .collect::<String>();
+    std::fs::write(&long, &content)?;
+    let file = fileview::load_file(&long, &ViewOptions::new(3, OutputFormat::Plain))?;
+    assert_eq!(file.lines, vec!["line 1", "line 2", "line 3"]);
+    assert_eq!(file.total_lines, 10000);
+    assert_eq!(file.omitted, vec![(4, 10000)]);
+    assert_eq!(file.size, content.len() as u64);
+
+    // 改行コードの正規化は行ごとに適用されることを確認
+    let crlf = tmp_dir.path().join("crlf.txt");
+    std::fs::write(&crlf, "a\r\r\nb\rc\nd\r")?;
+    let options = ViewOptions {
+        normalize: Normalize {
+            eol: Eol::Lf,
+            ..Normalize::default()
+        },
+        ..ViewOptions::new(2, OutputFormat::Plain)
+    };
+    let file = fileview::load_file(&crlf, &options)?;
+    assert_eq!(file.lines, vec!["a", "b"]);
+    assert_eq!(file.total_lines, 4);
+    let file = fileview::load_file(&crlf, &ViewOptions::new(0, OutputFormat::Plain))?;
+    assert_eq!(file.lines, vec!["a\r", "b\rc", "d\r"]);
+
+    // 表示しない行にUTF-8として読み込めないバイトがある場合は、ファイル全体から文字コードを推測することを確認
+    let sjis = tmp_dir.path().join("sjis.txt");
+    let mut bytes = b"first line\n".to_vec();
+    bytes.extend_from_slice(&encoding_rs::SHIFT_JIS.encode("こんにちは\n世界\n").0);
+    std::fs::write(&sjis, bytes)?;
+    let file = fileview::load_file(&sjis, &ViewOptions::new(1, OutputFormat::Plain))?;
+    assert_eq!(file.lines, vec!["first line"]);
+    assert_eq!(file.total_lines, 3);
+    assert_eq!(file.encoding, Some("Shift_JIS"));
+
+    Ok(())
+}
+
+#[test]
+fn test_normalize_whitespace() -> Result<()> {
+    let tmp_dir = TempDir::new()?;
+    let path = tmp_dir.path().join("messy.txt");
+    std::fs::write(&path, "a\r\r\nb  \r\n\tc\td\r\n\r\n  \r\n\r\ne\rf\n")?;
+
+    let render = |normalize: Normalize| -> Result<String> {
+        let options = ViewOptions {
+            normalize,
+            ..ViewOptions::new(0, OutputFormat::Plain)
+        };
+        let mut output = Vec::new();
+        fileview::file_view(&path, &mut output, &options)?;
+        Ok(String::from_utf8(output)?)
+    };
+
+    // 既定では余分なCRが残ることを確認
+    let kept = render(Normalize::default())?;
+    assert!(kept.contains("   1 | a\r\n"));
+    assert!(kept.contains("   7 | e\rf\n"));
+
+    // 余分なCRを取り除き、単独のCRを改行として扱うことを確認
+    let lf = render(Normalize {
+        eol: Eol::Lf,
+        ..Normalize::default()
+    })?;
+    assert!(lf.contains("   1 | a\n   2 | b  \n"));
+    assert!(lf.contains("   7 | e\n   8 | f\n"));
+
+    // タブを展開し、行末の空白を取り除き、空行をまとめても元の行番号が保たれることを確認
+    let all = render(Normalize {
+        eol: Eol::Lf,
+        tab_width: Some(4),
+        strip_trailing_ws: true,
+        squeeze_blank: true,
+    })?;
+    assert!(all.contains("   2 | b\n   3 |     c   d\n   4 | \n   7 | e\n"));
+
+    // 有効な正規化ごとに、それだけを無効にした設定が得られることを確認
+    let normalize = Normalize {
+        tab_width: Some(4),
+        squeeze_blank: true,
+        ..Normalize::default()
+    };
+    let labels = normalize
+        .each_enabled()
+        .into_iter()
+        .map(|(label, without)| {
+            assert_ne!(without, normalize);
+            label
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(labels, vec!["--tab-width 4", "--squeeze-blank"]);
+
+    assert_eq!(Eol::Lf.normalize("+x\r\n-y\r\n"), "+x\n-y\n");
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use std::path::Path;
+
+use codicat::generated::{RuleConfig, RuleSet};
+
+// ファイルの先頭部分を指定してマッチしたルール名を返す
+fn find<'a>(rules: &'a RuleSet, path: &str, header: &str) -> Option<&'a str> {
+    rules.find(Path::new(path), || Some(header.as_bytes().to_vec()))
+}
+
+#[test]
+fn test_builtin_rules() -> Result<()> {
+    let rules = RuleSet::default();
+
+    // ツール固有のシグネチャが汎用のルールより優先されることを確認
+    assert_eq!(
+        find(
+            &rules,
+            "db/query.sql.go",
+            "// Code generated by sqlc. DO NOT EDIT.\n"
+        ),
+        Some("sqlc")
+    );
+    assert_eq!(
+        find(
+            &rules,
+            "mock.go",
+            "// Code generated by MockGen. DO NOT EDIT.\n"
+        ),
+        Some("go-generated")
+    );
+    assert_eq!(
+        find(&rules, "api/user_pb2.py", "# -*- coding: utf-8 -*-\n"),
+        Some("protoc")
+    );
+    assert_eq!(
+        find(
+            &rules,
+            "src/bindings.rs",
+            "/* automatically generated by rust-bindgen 0.69.4 */\n"
+        ),
+        Some("bindgen")
+    );
+    assert_eq!(
+        find(&rules, "client/.openapi-generator/VERSION", "7.0.0\n"),
+        Some("openapi")
+    );
+
+    // パスだけで判定できるルール
+    assert_eq!(find(&rules, "web/Cargo.lock", ""), Some("lockfile"));
+    assert_eq!(find(&rules, "dist/app.js.map", ""), Some("source-map"));
+    assert_eq!(find(&rules, "dist/app.min.js", ""), Some("minified"));
+
+    // 長い行を含むJavaScriptは圧縮されたファイルとみなす
+    assert_eq!(
+        find(&rules, "dist/app.js", &"a=1;".repeat(200)),
+        Some("minified")
+    );
+    assert_eq!(find(&rules, "src/app.js", "console.log(1);\n"), None);
+
+    Ok(())
+}
+
+#[test]
+fn test_generated_marker_false_positives() -> Result<()> {
+    let rules = RuleSet::default();
+
+    // 自動生成を示すコメント
+    assert!(find(&rules, "a.rs", "// Auto-generated by build.rs\n").is_some());
+    assert!(find(&rules, "a.py", "# This file was automatically generated\n").is_some());
+    assert!(find(&rules, "a.js", "/**\n * @generated\n */\n").is_some());
+    assert!(find(&rules, "a.ts", "// Generated file. Do not edit.\n").is_some());
+
+    // 説明文中の「generated by」は自動生成とみなさないことを確認
+    assert!(find(
+        &rules,
+        "a.rs",
+        "/// Token generated by the user\nfn a() {}\n"
+    )
+    .is_none());
+    assert!(find(
+        &rules,
+        "a.rs",
+        "//! Reports are generated by the scheduler.\n"
+    )
+    .is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_custom_and_disabled_rules() -> Result<()> {
+    let custom = vec![RuleConfig {
+        name: "graphql".to_string(),
+        paths: vec!["src/gql/".to_string()],
+        header: Some("THIS IS GENERATED".to_string()),
+    }];
+    let rules = RuleSet::new(&custom, &["lockfile".to_string()])?;
+
+    // パスと正規表現の両方を満たす場合のみマッチすることを確認
+    assert_eq!(
+        find(&rules, "src/gql/types.ts", "// THIS IS GENERATED\n"),
+        Some("graphql")
+    );
+    assert_eq!(find(&rules, "src/gql/types.ts", "// hand written\n"), None);
+    assert_eq!(find(&rules, "src/types.ts", "// THIS IS GENERATED\n"), None);
+
+    // 無効にしたルールはマッチしないことを確認
+    assert_eq!(find(&rules, "Cargo.lock", ""), None);
+
+    // 存在しないルール名や条件のないルールはエラーになることを確認
+    assert!(RuleSet::new(&[], &["unknown".to_string()]).is_err());
+    let empty = vec![RuleConfig {
+        name: "empty".to_string(),
+        paths: Vec::new(),
+        header: None,
+    }];
+    assert!(RuleSet::new(&empty, &[]).is_err());
+
+    Ok(())
+}
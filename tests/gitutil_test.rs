@@ -1,10 +1,11 @@
 use anyhow::Result;
 use std::fs::File;
 use std::io::Write;
+use std::path::PathBuf;
 use std::process::Command;
 use tempfile::TempDir;
 
-use codicat::gitutil;
+use codicat::gitutil::{self, ChangeStatus, DiffSpec, Pathspec};
 
 // テスト用のGitリポジトリをセットアップする
 fn setup_git_repo() -> Result<TempDir> {
@@ -244,3 +245,354 @@ fn test_japanese_filename_handling() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_list_changed_files() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    // 変更・追加・削除を行う
+    std::fs::write(repo.path().join("a.txt"), "modified content")?;
+    std::fs::write(repo.path().join("new.txt"), "new content")?;
+    Command::new("git")
+        .args(["add", "a.txt", "new.txt"])
+        .current_dir(repo.path())
+        .output()?;
+    Command::new("git")
+        .args(["rm", "-q", "b.txt"])
+        .current_dir(repo.path())
+        .output()?;
+
+    let changes =
+        gitutil::list_changed_files(&Pathspec::new(repo.path(), Vec::new()), &DiffSpec::Staged)?;
+    let root = repo.path().canonicalize()?;
+
+    // 変更の種類とパスが正しく取得できることを確認
+    let statuses = changes
+        .iter()
+        .map(|c| (c.path.strip_prefix(&root).unwrap().to_path_buf(), c.status))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        statuses,
+        vec![
+            ("a.txt".into(), ChangeStatus::Modified),
+            ("b.txt".into(), ChangeStatus::Deleted),
+            ("new.txt".into(), ChangeStatus::Added),
+        ]
+    );
+
+    // 未ステージの変更はないことを確認
+    let unstaged =
+        gitutil::list_changed_files(&Pathspec::new(repo.path(), Vec::new()), &DiffSpec::Unstaged)?;
+    assert!(unstaged.is_empty());
+
+    // HEADからの変更にはステージ済みの変更が含まれることを確認
+    let since_head = gitutil::list_changed_files(
+        &Pathspec::new(repo.path(), Vec::new()),
+        &DiffSpec::Since("HEAD".into()),
+    )?;
+    assert_eq!(since_head.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_list_changed_files_renamed() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    // 内容が一意なファイルをコミットしてから名前を変更する
+    std::fs::write(repo.path().join("sub/unique.txt"), "unique content\n")?;
+    Command::new("git")
+        .args(["add", "."])
+        .current_dir(repo.path())
+        .output()?;
+    Command::new("git")
+        .args(["commit", "-m", "Add unique file"])
+        .current_dir(repo.path())
+        .output()?;
+    Command::new("git")
+        .args(["mv", "sub/unique.txt", "sub/renamed.txt"])
+        .current_dir(repo.path())
+        .output()?;
+
+    let changes = gitutil::list_changed_files(
+        &Pathspec::new(repo.path(), Vec::new()),
+        &DiffSpec::Range("HEAD~1..HEAD".into()),
+    )?;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].status, ChangeStatus::Added);
+
+    let changes =
+        gitutil::list_changed_files(&Pathspec::new(repo.path(), Vec::new()), &DiffSpec::Staged)?;
+    let root = repo.path().canonicalize()?;
+
+    // 名前変更は変更後のパスと変更元のパスの両方が取得できることを確認
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].status, ChangeStatus::Renamed);
+    assert_eq!(changes[0].path, root.join("sub/renamed.txt"));
+    assert_eq!(changes[0].old_path, Some(root.join("sub/unique.txt")));
+
+    Ok(())
+}
+
+#[test]
+fn test_diff_file() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    std::fs::write(repo.path().join("a.txt"), "content\nadded line\n")?;
+
+    let changes =
+        gitutil::list_changed_files(&Pathspec::new(repo.path(), Vec::new()), &DiffSpec::Unstaged)?;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].status.marker(), "M");
+
+    // 統一diff形式の差分が取得できることを確認
+    let diff = gitutil::diff_file(repo.path(), &DiffSpec::Unstaged, &changes[0])?;
+    assert!(diff.contains("--- a/a.txt"));
+    assert!(diff.contains("+++ b/a.txt"));
+    assert!(diff.contains("+added line"));
+
+    Ok(())
+}
+
+#[test]
+fn test_list_changed_files_invalid_revision() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    // 存在しないリビジョンはエラーになることを確認
+    let result = gitutil::list_changed_files(
+        &Pathspec::new(repo.path(), Vec::new()),
+        &DiffSpec::Range("nope..HEAD".to_string()),
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_snapshot() -> Result<()> {
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+
+    // 作業ツリーでファイルを変更・削除してもスナップショットの内容は変わらないことを確認
+    std::fs::write(repo.path().join("a.txt"), "changed")?;
+    std::fs::remove_file(repo.path().join("b.txt"))?;
+
+    let snapshot = gitutil::Snapshot::new(repo.path(), "HEAD")?;
+    let mut files = snapshot.list_files(&Pathspec::default())?;
+    files.sort();
+    assert_eq!(
+        files,
+        vec![
+            git_root.join("a.txt"),
+            git_root.join("b.txt"),
+            git_root.join("sub/c.txt"),
+        ]
+    );
+
+    assert_eq!(snapshot.read(git_root.join("a.txt"))?, b"content");
+    assert_eq!(snapshot.read(git_root.join("b.txt"))?, b"content");
+
+    // 存在しないリビジョンはエラーになることを確認
+    assert!(gitutil::Snapshot::new(repo.path(), "nope").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_list_untracked_files() -> Result<()> {
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+
+    // 未追跡ファイルと.gitignoreで無視されるファイルを作成
+    std::fs::write(repo.path().join(".gitignore"), "*.log\n")?;
+    std::fs::write(repo.path().join("sub/new.txt"), "new")?;
+    std::fs::write(repo.path().join("debug.log"), "ignored")?;
+
+    // 無視されていない未追跡ファイルのみが取得できることを確認
+    let mut files = gitutil::list_untracked_files(&Pathspec::new(repo.path(), Vec::new()))?;
+    files.sort();
+    assert_eq!(
+        files,
+        vec![git_root.join(".gitignore"), git_root.join("sub/new.txt")]
+    );
+
+    // Git管理下のファイル一覧には含まれないことを確認
+    let tracked = gitutil::list_git_tracked_files(repo.path())?;
+    assert_eq!(tracked.len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_list_git_tracked_files_with_pathspec() -> Result<()> {
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+
+    // サブディレクトリとa.txtを指定し、b.txtの除外を指定してもよいことを確認
+    let pathspec = Pathspec::new(
+        repo.path(),
+        vec!["sub".into(), "a.txt".into(), ":!b.txt".into()],
+    );
+    let mut files = gitutil::list_git_tracked_files_in(&pathspec)?;
+    files.sort();
+    assert_eq!(
+        files,
+        vec![git_root.join("a.txt"), git_root.join("sub/c.txt")]
+    );
+
+    // 除外のみを指定した場合は実行ディレクトリ配下から除外されることを確認
+    let pathspec = Pathspec::new(repo.path().join("sub"), vec![":!c.txt".into()]);
+    let files = gitutil::list_git_tracked_files_in(&pathspec)?;
+    assert!(files.is_empty());
+
+    // スナップショットでも同じpathspecが使えることを確認
+    let snapshot = gitutil::Snapshot::new(repo.path(), "HEAD")?;
+    let pathspec = Pathspec::new(repo.path(), vec!["*.txt".into(), ":!sub".into()]);
+    let mut files = snapshot.list_files(&pathspec)?;
+    files.sort();
+    assert_eq!(files, vec![git_root.join("a.txt"), git_root.join("b.txt")]);
+
+    // 絶対パスだけのpathspecはインデックス・ツリーの一覧から同じファイルが絞り込まれることを確認
+    let pathspec = Pathspec::new(
+        git_root.join("sub"),
+        vec![
+            git_root.join("sub").into_os_string(),
+            git_root.join("a.txt").into_os_string(),
+        ],
+    );
+    let mut files = gitutil::list_git_tracked_files_in(&pathspec)?;
+    files.sort();
+    assert_eq!(
+        files,
+        vec![git_root.join("a.txt"), git_root.join("sub/c.txt")]
+    );
+    let mut files = snapshot.list_files(&pathspec)?;
+    files.sort();
+    assert_eq!(
+        files,
+        vec![git_root.join("a.txt"), git_root.join("sub/c.txt")]
+    );
+
+    // ディレクトリ名の前方一致（sub2など）は含まれないことを確認
+    let pathspec = Pathspec::new(&git_root, vec![git_root.join("su").into_os_string()]);
+    assert!(gitutil::list_git_tracked_files_in(&pathspec)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_check_attributes() -> Result<()> {
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+    std::fs::write(
+        git_root.join(".gitattributes"),
+        "a.txt linguist-generated\nsub/** -diff linguist-vendored=true\n",
+    )?;
+
+    let files = gitutil::list_git_tracked_files(&git_root)?;
+    let attributes = gitutil::check_attributes(
+        &git_root,
+        &files,
+        &["linguist-generated", "linguist-vendored", "diff"],
+        None,
+    )?;
+
+    // 指定された属性のみが値とともに取得されることを確認
+    let a = &attributes[&git_root.join("a.txt")];
+    assert_eq!(a.len(), 1);
+    assert_eq!(a["linguist-generated"], "set");
+
+    let c = &attributes[&git_root.join("sub/c.txt")];
+    assert_eq!(c["diff"], "unset");
+    assert_eq!(c["linguist-vendored"], "true");
+
+    // 属性が指定されていないファイルは含まれないことを確認
+    assert!(!attributes.contains_key(&git_root.join("b.txt")));
+
+    Ok(())
+}
+
+#[test]
+fn test_check_attributes_from_snapshot() -> Result<()> {
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+
+    // コミット・インデックス・作業ツリーでそれぞれ異なる`.gitattributes`にする
+    let attributes_file = git_root.join(".gitattributes");
+    std::fs::write(&attributes_file, "a.txt linguist-generated\n")?;
+    Command::new("git")
+        .args(["add", ".gitattributes"])
+        .current_dir(&git_root)
+        .output()?;
+    Command::new("git")
+        .args(["commit", "-qm", "Add attributes"])
+        .current_dir(&git_root)
+        .output()?;
+    std::fs::write(&attributes_file, "b.txt linguist-generated\n")?;
+    Command::new("git")
+        .args(["add", ".gitattributes"])
+        .current_dir(&git_root)
+        .output()?;
+    std::fs::write(&attributes_file, "sub/c.txt linguist-generated\n")?;
+
+    let files = gitutil::list_git_tracked_files(&git_root)?;
+    let generated = |snapshot: Option<&gitutil::Snapshot>| -> Result<Vec<PathBuf>> {
+        let attributes =
+            gitutil::check_attributes(&git_root, &files, &["linguist-generated"], snapshot)?;
+        let mut paths = attributes.into_keys().collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    };
+
+    // スナップショットに応じた`.gitattributes`から属性が取得されることを確認
+    let head = gitutil::Snapshot::new(&git_root, "HEAD")?;
+    assert_eq!(generated(Some(&head))?, vec![git_root.join("a.txt")]);
+    let index = gitutil::Snapshot::index(&git_root)?;
+    assert_eq!(generated(Some(&index))?, vec![git_root.join("b.txt")]);
+    assert_eq!(generated(None)?, vec![git_root.join("sub/c.txt")]);
+
+    Ok(())
+}
+
+#[cfg(feature = "native-git")]
+#[test]
+fn test_native_backend_matches_cli() -> Result<()> {
+    use gitutil::{Backend, CliBackend, NativeBackend};
+
+    let repo = setup_git_repo()?;
+    let git_root = repo.path().canonicalize()?;
+
+    // 未コミットのステージ済みファイルと日本語のファイル名も含める
+    std::fs::write(git_root.join("日本語.txt"), "staged")?;
+    Command::new("git")
+        .args(["add", "日本語.txt"])
+        .current_dir(&git_root)
+        .output()?;
+
+    let native = NativeBackend;
+    let cli = CliBackend;
+
+    // サブディレクトリからもGitルートが同じように解決されることを確認
+    let sub = git_root.join("sub");
+    assert_eq!(native.discover_root(&sub)?, cli.discover_root(&sub)?);
+    assert!(native.discover_root(&std::env::temp_dir()).is_err());
+
+    // インデックス・ツリー・blobの読み込み結果が一致することを確認
+    assert_eq!(native.list_index(&git_root)?, cli.list_index(&git_root)?);
+    assert_eq!(
+        native.list_tree(&git_root, "HEAD")?,
+        cli.list_tree(&git_root, "HEAD")?
+    );
+    assert_eq!(
+        native.read_blob(&git_root, "HEAD", "sub/c.txt")?,
+        cli.read_blob(&git_root, "HEAD", "sub/c.txt")?
+    );
+    assert!(native.has_revision(&git_root, "HEAD")?);
+
+    // 存在しないリビジョンやファイルはエラーになることを確認
+    assert!(native.has_revision(&git_root, "nope").is_err());
+    assert!(native.read_blob(&git_root, "HEAD", "nope.txt").is_err());
+    assert!(native.read_blob(&git_root, "HEAD", "sub").is_err());
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+
+use codicat::config::Settings;
+use codicat::limits::{self, ByteSize, TotalBytes};
+
+#[test]
+fn test_parse_byte_size() -> Result<()> {
+    // 単位なしはバイト、K・M・Gは1024の累乗として解釈されることを確認
+    assert_eq!("1048576".parse::<ByteSize>()?, ByteSize(1 << 20));
+    assert_eq!("512K".parse::<ByteSize>()?, ByteSize(512 << 10));
+    assert_eq!("10mb".parse::<ByteSize>()?, ByteSize(10 << 20));
+    assert_eq!("1GiB".parse::<ByteSize>()?, ByteSize(1 << 30));
+
+    // 不正な書式はエラーになることを確認
+    assert!("".parse::<ByteSize>().is_err());
+    assert!("10X".parse::<ByteSize>().is_err());
+    assert!("1.5M".parse::<ByteSize>().is_err());
+    assert!("99999999999999999999G".parse::<ByteSize>().is_err());
+
+    // 割り切れる最大の単位で表示されることを確認
+    assert_eq!(ByteSize(10 << 20).to_string(), "10M");
+    assert_eq!(ByteSize(1536).to_string(), "1536");
+    assert_eq!(ByteSize(0).to_string(), "0");
+
+    Ok(())
+}
+
+#[test]
+fn test_byte_size_in_config() -> Result<()> {
+    // 設定ファイルでは数値と単位付きの文字列のどちらも指定できることを確認
+    let settings: Settings = toml::from_str("max-file-size = \"1M\"\nmax-total-bytes = 4096\n")?;
+    assert_eq!(settings.max_file_size, Some(ByteSize(1 << 20)));
+    assert_eq!(settings.max_total_bytes, Some(ByteSize(4096)));
+
+    // 単位付きの文字列として書き出されることを確認
+    assert!(toml::to_string(&settings)?.contains("max-total-bytes = \"4K\""));
+
+    assert!(toml::from_str::<Settings>("max-file-size = \"big\"").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_human_size() {
+    assert_eq!(limits::human_size(812), "812 B");
+    assert_eq!(limits::human_size(1536), "1.5 KiB");
+    assert_eq!(limits::human_size(40 << 20), "40.0 MiB");
+}
+
+#[test]
+fn test_total_bytes() {
+    // 既に出力したバイト数を含めて上限を超えると拒否され、以降は収まる大きさでも拒否されることを確認
+    let mut total = TotalBytes::new(Some(100), 30);
+    assert!(total.take(50));
+    assert!(!total.take(30));
+    assert!(!total.take(20));
+    assert!(!total.take(1));
+    assert_eq!(total.reason(), "skipped: --max-total-bytes 100 reached");
+
+    // 上限ちょうどまでは受け付けることを確認
+    let mut total = TotalBytes::new(Some(100), 0);
+    assert!(total.take(100));
+    assert!(!total.take(1));
+
+    let mut unlimited = TotalBytes::new(None, 0);
+    assert!(unlimited.take(u64::MAX / 2));
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use codicat::parallel;
+
+#[test]
+fn test_map_ordered_keeps_order() -> Result<()> {
+    let items = (0..200).collect::<Vec<u64>>();
+
+    // 処理時間がばらついても結果は元の順序で返されることを確認
+    for jobs in [0, 1, 4] {
+        let results = parallel::map_ordered(
+            &items,
+            jobs,
+            |n| {
+                std::thread::sleep(Duration::from_micros((n * 37) % 200));
+                n * 2
+            },
+            |results| results.collect::<Vec<_>>(),
+        );
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_map_ordered_stops_early() -> Result<()> {
+    let items = (0..1000).collect::<Vec<u64>>();
+    let processed = AtomicUsize::new(0);
+
+    // 結果を途中までしか読まない場合、先行して処理される要素数は限られることを確認
+    let first = parallel::map_ordered(
+        &items,
+        4,
+        |n| {
+            processed.fetch_add(1, Ordering::Relaxed);
+            *n
+        },
+        |results| results.take(3).collect::<Vec<_>>(),
+    );
+    assert_eq!(first, vec![0, 1, 2]);
+    assert!(processed.load(Ordering::Relaxed) < 100);
+
+    // 空の入力でも動作することを確認
+    let empty: Vec<u64> = parallel::map_ordered(&[], 4, |n: &u64| *n, |r| r.collect());
+    assert!(empty.is_empty());
+
+    Ok(())
+}
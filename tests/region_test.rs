@@ -0,0 +1,173 @@
+use anyhow::Result;
+
+use codicat::region::Region;
+
+// テスト用のソースを行に分割する
+fn lines(source: &str) -> Vec<String> {
+    source.lines().map(String::from).collect()
+}
+
+#[test]
+fn test_parse_region() -> Result<()> {
+    assert_eq!(
+        Region::parse("src/cli.rs:40-120")?,
+        Some((
+            "src/cli.rs",
+            Region::Lines {
+                start: 40,
+                end: Some(120)
+            }
+        ))
+    );
+    assert_eq!(
+        Region::parse("src/cli.rs:120-")?,
+        Some((
+            "src/cli.rs",
+            Region::Lines {
+                start: 120,
+                end: None
+            }
+        ))
+    );
+    assert_eq!(
+        Region::parse("src/cli.rs:7")?,
+        Some((
+            "src/cli.rs",
+            Region::Lines {
+                start: 7,
+                end: Some(7)
+            }
+        ))
+    );
+    assert_eq!(
+        Region::parse("src/cli.rs#App::execute")?,
+        Some(("src/cli.rs", Region::Symbol("App::execute".to_string())))
+    );
+
+    // 範囲の書式でない引数はpathspecとして扱うためNoneになることを確認
+    assert_eq!(Region::parse("src/cli.rs")?, None);
+    assert_eq!(Region::parse(":!*.snap")?, None);
+    assert_eq!(Region::parse("notes#1.md")?, None);
+
+    // 不正な行範囲はエラーになることを確認
+    assert!(Region::parse("src/cli.rs:0-3").is_err());
+    assert!(Region::parse("src/cli.rs:9-3").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_locate_lines() -> Result<()> {
+    let source = lines("a\nb\nc\nd\n");
+
+    assert_eq!(
+        Region::Lines {
+            start: 2,
+            end: Some(3)
+        }
+        .locate(&source)?,
+        (2, 3)
+    );
+    assert_eq!(
+        Region::Lines {
+            start: 3,
+            end: None
+        }
+        .locate(&source)?,
+        (3, 4)
+    );
+    // ファイルの最後を超える終了行は最後の行までになることを確認
+    assert_eq!(
+        Region::Lines {
+            start: 3,
+            end: Some(99)
+        }
+        .locate(&source)?,
+        (3, 4)
+    );
+    assert!(Region::Lines {
+        start: 5,
+        end: None
+    }
+    .locate(&source)
+    .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_locate_symbol() -> Result<()> {
+    let source = lines(
+        r#"pub struct App {
+    name: String,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl App {
+    /// 新しいアプリケーションを作成する
+    pub fn new() -> Self {
+        App { name: "}".to_string() }
+    }
+
+    #[inline]
+    pub fn execute(&self, buf: [u8; 4]) -> usize {
+        let c = '{';
+        // }
+        buf.len()
+    }
+}
+
+fn helper() {}
+"#,
+    );
+
+    // implブロックのメソッドはドキュメントコメント・属性を含めて取り出されることを確認
+    let execute = Region::Symbol("App::execute".to_string());
+    assert_eq!(execute.locate(&source)?, (17, 22));
+    assert_eq!(
+        Region::Symbol("App::new".to_string()).locate(&source)?,
+        (12, 15)
+    );
+    assert_eq!(
+        Region::Symbol("App::default".to_string()).locate(&source)?,
+        (6, 8)
+    );
+
+    // 型名のみの場合は構造体よりimplブロックを優先することを確認
+    assert_eq!(Region::Symbol("App".to_string()).locate(&source)?, (11, 23));
+    assert_eq!(
+        Region::Symbol("helper".to_string()).locate(&source)?,
+        (25, 25)
+    );
+
+    // 見つからないシンボルはエラーになることを確認
+    assert!(Region::Symbol("App::missing".to_string())
+        .locate(&source)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_locate_indented_symbol() -> Result<()> {
+    let source = lines(
+        "class Parser:\n    def parse(self):\n        return 1\n\n    def reset(self):\n        pass\n\nx = 1\n",
+    );
+
+    // インデントで表されたブロックも取り出せることを確認
+    assert_eq!(
+        Region::Symbol("Parser.parse".to_string()).locate(&source)?,
+        (2, 3)
+    );
+    assert_eq!(
+        Region::Symbol("Parser".to_string()).locate(&source)?,
+        (1, 6)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use anyhow::Result;
+
+use codicat::tokens::{Encoding, TokenCounter};
+
+#[test]
+fn test_approx_token_count() -> Result<()> {
+    let counter = TokenCounter::new(Encoding::Approx)?;
+
+    // 4文字を1トークンとして切り上げで計算されることを確認
+    assert_eq!(counter.count(""), 0);
+    assert_eq!(counter.count("abcd"), 1);
+    assert_eq!(counter.count("abcde"), 2);
+    assert_eq!(counter.count("日本語です"), 2);
+    assert_eq!(counter.encoding().name(), "approx");
+
+    Ok(())
+}
+
+#[test]
+fn test_bpe_encodings() -> Result<()> {
+    let text = "fn main() {\n    println!(\"Hello, world!\");\n}\n";
+
+    // 各エンコーディングでトークン数が計算できることを確認
+    for encoding in [
+        Encoding::O200kBase,
+        Encoding::Cl100kBase,
+        Encoding::P50kBase,
+    ] {
+        let counter = TokenCounter::new(encoding)?;
+        let tokens = counter.count(text);
+        assert!(tokens > 0 && tokens < text.len());
+        assert_eq!(counter.encoding(), encoding);
+    }
+
+    Ok(())
+}
@@ -145,3 +145,52 @@ fn test_subdirectory_tree() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_tree_token_annotation() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    let mut tree = treeview::build_tree_from_git(repo.path())?;
+
+    // 各ファイルを10トークンとして集計する
+    let total = tree.annotate_tokens(&repo.path().canonicalize()?, &mut |_| 10);
+    assert_eq!(total, 40);
+
+    let mut buf = Vec::new();
+    treeview::print_tree(&tree, &mut buf, true)?;
+    let output = String::from_utf8(buf)?;
+
+    // ファイルにはトークン数、ディレクトリには配下の合計が表示されることを確認
+    assert!(output.contains("(40 tokens)\n"));
+    assert!(output.contains("a.txt (10 tokens)"));
+    assert!(output.contains("sub (20 tokens)"));
+    assert!(output.contains("sub2 (10 tokens)"));
+
+    // 上位のファイルとディレクトリを取得できることを確認
+    let (files, dirs) = tree.heaviest(1);
+    assert_eq!(files, vec![("a.txt".to_string(), 10)]);
+    assert_eq!(dirs, vec![("sub/".to_string(), 20)]);
+
+    Ok(())
+}
+
+#[test]
+fn test_tree_highlight() -> Result<()> {
+    let repo = setup_git_repo()?;
+
+    let mut tree = treeview::build_tree_from_git(repo.path())?;
+
+    // a.txtのみを強調表示する
+    tree.highlight(&repo.path().canonicalize()?, &mut |path| {
+        path.ends_with("a.txt")
+    });
+
+    let mut buf = Vec::new();
+    treeview::print_tree(&tree, &mut buf, false)?;
+    let output = String::from_utf8(buf)?;
+
+    assert!(output.contains("a.txt *\n"));
+    assert!(output.contains("b.txt\n"));
+
+    Ok(())
+}